@@ -0,0 +1,129 @@
+//! Caches for the results of futures.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use {Future, SharedFuture};
+
+/// Caches a shared future per key along with the version it was computed at. Callers
+/// ask for a minimum acceptable version, an epoch, and anything older is recomputed.
+/// Useful when stale reads are only fine up to a point, say below a Lamport timestamp.
+///
+/// ```
+/// use tangle::Future;
+/// use tangle::cache::VersionedCache;
+///
+/// let cache = VersionedCache::<&str, u32>::new();
+///
+/// let first = cache.get_or_insert("config", 1, || Future::unit(10));
+/// let cached = cache.get_or_insert("config", 1, || Future::unit(20));
+/// let newer = cache.get_or_insert("config", 2, || Future::unit(30));
+///
+/// assert_eq!(first.wait().unwrap(), 10);
+/// assert_eq!(cached.wait().unwrap(), 10);
+/// assert_eq!(newer.wait().unwrap(), 30);
+/// ```
+#[derive(Debug)]
+pub struct VersionedCache<K, T, E=()> {
+    entries: Mutex<HashMap<K, (u64, SharedFuture<T, E>)>>
+}
+
+impl<K, T, E> VersionedCache<K, T, E>
+    where K: Eq + Hash,
+          T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    pub fn new() -> VersionedCache<K, T, E> {
+        VersionedCache { entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// The cached future for `key` if it was computed at `epoch` or later, otherwise
+    /// the one returned by `f`, which is cached under `epoch` from then on. An entry
+    /// whose computation went away without resolving is replaced the same way.
+    pub fn get_or_insert<F>(&self, key: K, epoch: u64, f: F) -> SharedFuture<T, E>
+        where F: FnOnce() -> Future<T, E>
+    {
+        let mut entries = self.entries.lock().expect("error acquiring a lock.");
+
+        if let Some(&(version, ref future)) = entries.get(&key) {
+            if version >= epoch && !future.is_gone() {
+                return future.clone();
+            }
+        }
+
+        let future = f().shared();
+        entries.insert(key, (epoch, future.clone()));
+        future
+    }
+
+    /// The version `key` is cached at, if it's cached at all.
+    pub fn version(&self, key: &K) -> Option<u64> {
+        self.entries.lock().expect("error acquiring a lock.").get(key).map(|&(version, _)| version)
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        self.entries.lock().expect("error acquiring a lock.").remove(key);
+    }
+}
+
+impl<K, T, E> Default for VersionedCache<K, T, E>
+    where K: Eq + Hash,
+          T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    fn default() -> VersionedCache<K, T, E> {
+        VersionedCache::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Future, Promise};
+
+    #[test]
+    fn reuses_fresh_entries() {
+        let cache = VersionedCache::<u32, u32>::new();
+
+        cache.get_or_insert(1, 5, || Future::unit(1));
+        let older = cache.get_or_insert(1, 3, || panic!("should be cached"));
+
+        assert_eq!(older.wait().unwrap(), 1);
+        assert_eq!(cache.version(&1), Some(5));
+    }
+
+    #[test]
+    fn recomputes_stale_entries() {
+        let cache = VersionedCache::<u32, u32, u32>::new();
+
+        cache.get_or_insert(1, 1, || Future::err(1));
+        let fresh = cache.get_or_insert(1, 2, || Future::unit(2));
+
+        assert_eq!(fresh.wait().unwrap(), 2);
+        assert_eq!(cache.version(&1), Some(2));
+    }
+
+    #[test]
+    fn replaces_gone_entries() {
+        let cache = VersionedCache::<u32, u32>::new();
+
+        let gone = cache.get_or_insert(1, 1, || {
+            let mut promise = Promise::new();
+            promise.future()
+        });
+
+        assert!(gone.is_gone());
+        assert_eq!(cache.get_or_insert(1, 1, || Future::unit(2)).wait().unwrap(), 2);
+    }
+
+    #[test]
+    fn invalidate() {
+        let cache = VersionedCache::<u32, u32>::new();
+
+        cache.get_or_insert(1, 1, || Future::unit(1));
+        cache.invalidate(&1);
+
+        assert_eq!(cache.version(&1), None);
+    }
+}
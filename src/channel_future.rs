@@ -0,0 +1,256 @@
+//! A `Future` counterpart backed by `crossbeam-channel` instead of `std::sync::mpsc`.
+//! The receiver can take part in crossbeam's `select!` and the sending half may be
+//! cloned and shared between several producers. Enabled with the `crossbeam` feature.
+
+use std::sync::mpsc::channel;
+use std::thread;
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError, unbounded};
+
+use {Async, Continue, Future, global_pool};
+
+/// A future whose value travels over a crossbeam channel. Only the basic combinators
+/// live here, `into_future` hands the value to a regular `Future` for the rest.
+#[derive(Debug)]
+pub struct ChannelFuture<T, E=()> {
+    receiver: Receiver<Async<T, E>>
+}
+
+impl<T, E> ChannelFuture<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// ```
+    /// use tangle::Async;
+    /// use tangle::channel_future::ChannelFuture;
+    ///
+    /// let f: ChannelFuture<u32> = ChannelFuture::new(|| Async::Ok(5));
+    /// assert_eq!(f.recv(), Ok(5));
+    /// ```
+    pub fn new<F>(f: F) -> ChannelFuture<T, E>
+        where F: FnOnce() -> Async<T, E> + Send + 'static
+    {
+        let (tx, rx) = unbounded();
+
+        global_pool().execute(move || { let _ = tx.send(f()); });
+
+        ChannelFuture { receiver: rx }
+    }
+
+    pub fn from_receiver(receiver: Receiver<Async<T, E>>) -> ChannelFuture<T, E> {
+        ChannelFuture { receiver }
+    }
+
+    /// Create a future along with the sending end of its channel. The sender can be
+    /// cloned freely, whichever value is sent first resolves the future and the rest
+    /// are dropped.
+    ///
+    /// ```
+    /// use tangle::Async;
+    /// use tangle::channel_future::ChannelFuture;
+    ///
+    /// let (tx, f) = ChannelFuture::<u32>::channel();
+    /// let other = tx.clone();
+    ///
+    /// other.send(Async::Ok(1)).unwrap();
+    /// assert_eq!(f.recv(), Ok(1));
+    /// ```
+    pub fn channel() -> (Sender<Async<T, E>>, ChannelFuture<T, E>) {
+        let (tx, rx) = unbounded();
+
+        (tx, ChannelFuture { receiver: rx })
+    }
+
+    pub fn unit(val: T) -> ChannelFuture<T, E> {
+        let (tx, rx) = unbounded();
+
+        let _ = tx.send(Async::Ok(val));
+
+        ChannelFuture { receiver: rx }
+    }
+
+    pub fn err(err: E) -> ChannelFuture<T, E> {
+        let (tx, rx) = unbounded();
+
+        let _ = tx.send(Async::Err(err));
+
+        ChannelFuture { receiver: rx }
+    }
+
+    /// The receiving end of the channel, for use with crossbeam's `select!`.
+    pub fn receiver(&self) -> &Receiver<Async<T, E>> {
+        &self.receiver
+    }
+
+    pub fn and_then<F, S>(self, f: F) -> ChannelFuture<S, E>
+        where F: FnOnce(T) -> Async<S, E> + Send + 'static,
+              S: Send + 'static
+    {
+        self.process(move |val| match val {
+            Async::Ok(val) => f(val),
+            Async::Err(err) => Async::Err(err),
+            Continue(next) => Continue(next.and_then(f))
+        })
+    }
+
+    pub fn map<F, S>(self, f: F) -> ChannelFuture<S, E>
+        where F: FnOnce(T) -> S + Send + 'static,
+              S: Send + 'static
+    {
+        self.and_then(move |val| Async::Ok(f(val)))
+    }
+
+    /// Same as `wait`, only needs to be called as `r#await` from the 2018 edition on.
+    pub fn await(self) -> Async<T, E> {
+        self.wait()
+    }
+
+    pub fn wait(self) -> Async<T, E> {
+        match self.receiver.recv().expect("error trying to wait for channel.") {
+            Continue(f) => f.wait(),
+            val => val
+        }
+    }
+
+    pub fn recv(self) -> Result<T, E> {
+        match self.wait() {
+            Async::Ok(val) => Ok(val),
+            Async::Err(err) => Err(err),
+            Continue(_) => unreachable!()
+        }
+    }
+
+    /// Turn this into a regular `Future` to use the rest of the combinators.
+    pub fn into_future(self) -> Future<T, E> {
+        let (tx, rx) = channel();
+
+        match self.receiver.try_recv() {
+            Ok(val) => { let _ = tx.send(val); },
+            Err(_) => {
+                thread::spawn(move || {
+                    if let Ok(val) = self.receiver.recv() {
+                        let _ = tx.send(val);
+                    }
+                });
+            }
+        }
+
+        Future::from_async_channel(rx)
+    }
+
+    /// Hand whatever this future sends to `f` on the pool. A value that hasn't
+    /// arrived yet is waited for on a thread of its own, there's no leaving a
+    /// callback with a crossbeam channel, so no pool thread blocks on work that may
+    /// only be submitted after it.
+    fn process<S, U, F>(self, f: F) -> ChannelFuture<S, U>
+        where F: FnOnce(Async<T, E>) -> Async<S, U> + Send + 'static,
+              S: Send + 'static,
+              U: Send + 'static
+    {
+        ChannelFuture::new(move || {
+            match self.receiver.try_recv() {
+                Ok(val) => f(val),
+                Err(TryRecvError::Empty) => {
+                    let (tx, next) = channel();
+
+                    thread::spawn(move || {
+                        let val = self.receiver.recv().expect("error trying to wait for channel.");
+
+                        global_pool().execute(move || { let _ = tx.send(f(val)); });
+                    });
+
+                    Continue(Future::from_async_channel(next))
+                },
+                Err(TryRecvError::Disconnected) => panic!("error trying to wait for channel.")
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::select;
+    use {Async, Continue, Future};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn chain() {
+        let f: ChannelFuture<u32> = ChannelFuture::unit(5);
+        let f = f.map(|n| n + 1).and_then(|n| Async::Ok(n * 2));
+
+        assert_eq!(f.recv(), Ok(12));
+    }
+
+    #[test]
+    fn continue_chain() {
+        let f: ChannelFuture<u32> = ChannelFuture::new(|| Async::Ok(1));
+        let f = f.and_then(|n| Continue(Future::new(move || {
+            thread::sleep(Duration::from_millis(10));
+            Async::Ok(n + 1)
+        })));
+
+        assert_eq!(f.map(|n| n * 10).recv(), Ok(20));
+    }
+
+    #[test]
+    fn err_passes_through() {
+        let f: ChannelFuture<u32, u32> = ChannelFuture::err(3);
+
+        assert_eq!(f.map(|n| n + 1).recv(), Err(3));
+    }
+
+    #[test]
+    fn select_ready() {
+        let (_tx, pending) = ChannelFuture::<u32>::channel();
+        let ready: ChannelFuture<u32> = ChannelFuture::unit(2);
+
+        let val = select! {
+            recv(pending.receiver()) -> _ => panic!("Unexpected value"),
+            recv(ready.receiver()) -> val => val.unwrap()
+        };
+
+        assert_eq!(val.unwrap(), 2);
+    }
+
+    #[test]
+    fn into_future() {
+        let (tx, f) = ChannelFuture::<u32>::channel();
+        let f = f.into_future().map(|n| n + 1);
+
+        tx.send(Async::Ok(1)).unwrap();
+
+        assert_eq!(f.recv(), Ok(2));
+    }
+
+    #[test]
+    fn extra_sends_dont_block() {
+        let (tx, f) = ChannelFuture::<u32>::channel();
+        let other = tx.clone();
+
+        tx.send(Async::Ok(1)).unwrap();
+        other.send(Async::Ok(2)).unwrap();
+
+        assert_eq!(f.recv(), Ok(1));
+    }
+
+    #[test]
+    fn pending_and_then_leaves_the_pool_free() {
+        let (senders, pending): (Vec<_>, Vec<_>) = (0..::num_cpus::get() as u32).map(|n| {
+            let (tx, f) = ChannelFuture::<u32>::channel();
+            (tx, f.map(move |v| v + n))
+        }).unzip();
+
+        let other: ChannelFuture<u32> = ChannelFuture::new(|| Async::Ok(7));
+        assert_eq!(other.recv(), Ok(7));
+
+        for tx in senders {
+            tx.send(Async::Ok(1)).unwrap();
+        }
+
+        for (n, f) in pending.into_iter().enumerate() {
+            assert_eq!(f.recv(), Ok(1 + n as u32));
+        }
+    }
+}
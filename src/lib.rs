@@ -4,12 +4,24 @@
 //! an asynchronous equivalent to `Result<T, E>`, the only difference being that
 //! an extra variant `Continue(Future<T, E>)` allows for composition.
 
+#![allow(clippy::type_complexity)]
+
 use std::thread;
-use std::sync::mpsc::{Receiver, Sender, channel};
-use std::sync::Mutex;
+use std::cell::RefCell;
 use std::convert;
+use std::error;
+use std::fmt;
+use std::iter;
 use std::mem;
-use threadpool::ThreadPool;
+use std::vec;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError, channel};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::future::Future as StdFuture;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+use std::marker::PhantomData;
 
 pub use Async::Continue;
 
@@ -17,9 +29,77 @@ pub use Async::Continue;
 extern crate lazy_static;
 extern crate threadpool;
 extern crate num_cpus;
+#[cfg(feature = "crossbeam")]
+extern crate crossbeam_channel;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub mod cache;
+pub mod local_pool;
+mod oneshot;
+pub mod pool;
+pub mod stream;
+
+#[cfg(feature = "crossbeam")]
+pub mod channel_future;
+
+pub use pool::{Executor, FutureExecutor, FuturePool, PoolMetrics};
 
 lazy_static! {
-    static ref POOL: Mutex<ThreadPool> = Mutex::new(ThreadPool::new(num_cpus::get()));
+    static ref POOL: Mutex<Arc<FuturePool>> = Mutex::new(Arc::new(FuturePool::new(num_cpus::get())));
+}
+
+/// The pool every future created with `Future::new` runs on, sized to the number of
+/// CPUs unless it was replaced with `set_global_pool`.
+///
+/// ```
+/// let pool = tangle::global_pool();
+/// assert!(pool.metrics().threads > 0);
+/// ```
+pub fn global_pool() -> Arc<FuturePool> {
+    POOL.lock().expect("error acquiring a lock.").clone()
+}
+
+static MAX_CONTINUATION_DEPTH: AtomicUsize = AtomicUsize::new(1000);
+
+/// The limit `Future::with_default_recursion_limit` enforces, 1000 unless changed.
+pub fn max_continuation_depth() -> usize {
+    MAX_CONTINUATION_DEPTH.load(Ordering::SeqCst)
+}
+
+/// Only `with_default_recursion_limit` enforces the limit, `await` and `recv`
+/// follow a chain of any length without growing the stack.
+pub fn set_max_continuation_depth(n: usize) {
+    MAX_CONTINUATION_DEPTH.store(n, Ordering::SeqCst);
+}
+
+thread_local! {
+    static LABEL: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// The label of the `FutureBuilder` future whose closure is running on this thread,
+/// if any. Pool threads are shared and keep their own names, this is how the closure
+/// finds out which task it's running for, in log lines for example.
+///
+/// ```
+/// use tangle::{FutureBuilder, Async};
+///
+/// let f = FutureBuilder::<Option<String>>::new()
+///     .label("lookup")
+///     .build(|| Async::Ok(tangle::current_label()));
+///
+/// assert_eq!(f.recv(), Ok(Some("lookup".to_string())));
+/// assert_eq!(tangle::current_label(), None);
+/// ```
+pub fn current_label() -> Option<String> {
+    LABEL.with(|label| label.borrow().as_ref().map(|label| label.to_string()))
+}
+
+/// Replace the global pool. Futures that are already running stay on the old one,
+/// which shuts down once the last of them finishes and nothing else holds it.
+pub fn set_global_pool(pool: Arc<FuturePool>) {
+    *POOL.lock().expect("error acquiring a lock.") = pool;
 }
 
 /// Asynchronous version of `Result<T, E>` that allows for future composition. Additional
@@ -32,486 +112,5726 @@ pub enum Async<T, E> {
 }
 
 impl<T, E> Async<T, E> {
-    pub fn unwrap(self) -> T {
+    /// The `Ok` value. Panics with the error on `Err` and on a `Continue` that has
+    /// not resolved yet.
+    pub fn unwrap(self) -> T
+        where E: fmt::Debug
+    {
+        match self {
+            Async::Ok(t) => t,
+            Async::Err(e) => panic!("called `Async::unwrap()` on an `Err` value: {:?}", e),
+            Async::Continue(_) => panic!("called `Async::unwrap()` on `Continue`, the future has not resolved yet.")
+        }
+    }
+
+    /// The `Err` value. Panics with the value on `Ok` and on `Continue`.
+    ///
+    /// ```
+    /// use tangle::Async;
+    ///
+    /// let val: Async<u32, &str> = Async::Err("failed");
+    /// assert_eq!(val.unwrap_err(), "failed");
+    /// ```
+    pub fn unwrap_err(self) -> E
+        where T: fmt::Debug
+    {
+        match self {
+            Async::Ok(t) => panic!("called `Async::unwrap_err()` on an `Ok` value: {:?}", t),
+            Async::Err(e) => e,
+            Async::Continue(_) => panic!("called `Async::unwrap_err()` on `Continue`, the future has not resolved yet.")
+        }
+    }
+
+    /// Same as `unwrap`, with `msg` leading the panic message.
+    pub fn expect(self, msg: &str) -> T
+        where E: fmt::Debug
+    {
         match self {
             Async::Ok(t) => t,
-            _ => panic!("cannot unwrap `Async` of Err or Continue.")
+            Async::Err(e) => panic!("{}: {:?}", msg, e),
+            Async::Continue(_) => panic!("{}: the future has not resolved yet.", msg)
         }
     }
 
-    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Async<U, E> {
+    /// Map the success value. A `Continue` stays a `Continue`, with `f` applied once
+    /// its future resolves.
+    ///
+    /// ```
+    /// use tangle::{Async, Future};
+    ///
+    /// let val: Async<u32, ()> = Async::Continue(Future::unit(2));
+    /// assert_eq!(val.map(|n| n * 2).to_future().recv(), Ok(4));
+    /// ```
+    pub fn map<U, F>(self, f: F) -> Async<U, E>
+        where F: FnOnce(T) -> U + Send + 'static,
+              T: Send + 'static,
+              U: Send + 'static,
+              E: Send + 'static
+    {
         match self {
             Async::Ok(t) => Async::Ok(f(t)),
             Async::Err(e) => Async::Err(e),
-            Async::Continue(cont) => panic!("Cannot map on `Async::Continue`. Use `map_future` for that.")
+            Async::Continue(future) => Async::Continue(future.map(f))
         }
     }
 
-    pub fn map_err<U, F: FnOnce(E) -> U>(self, f: F) -> Async<T, U> {
+    /// Map the error, the same way `map` does for the success value.
+    pub fn map_err<U, F>(self, f: F) -> Async<T, U>
+        where F: FnOnce(E) -> U + Send + 'static,
+              T: Send + 'static,
+              U: Send + 'static,
+              E: Send + 'static
+    {
         match self {
             Async::Ok(t) => Async::Ok(t),
             Async::Err(e) => Async::Err(f(e)),
-            Async::Continue(cont) => panic!("Cannot map on `Async::Continue`. Use `map_future` for that.")
+            Async::Continue(future) => Async::Continue(future.map_err(f))
         }
     }
 
     pub fn is_err(&self) -> bool {
-        match self {
-            &Async::Err(_) => true,
-            _ => false
-        }
+        matches!(*self, Async::Err(_))
     }
 
     pub fn is_future(&self) -> bool {
-        match self {
-            &Async::Continue(_) => true,
-            _ => false
-        }
+        matches!(*self, Async::Continue(_))
     }
 
     pub fn is_ok(&self) -> bool {
-        match self {
-            &Async::Ok(_) => true,
-            _ => false
-        }
+        matches!(*self, Async::Ok(_))
     }
-}
-
-/// ok!(123)
-#[macro_export]
-macro_rules! ok {
-    ($expr:expr) => (Async::Ok($expr))
-}
 
-// err!(123)
-#[macro_export]
-macro_rules! err {
-    ($expr:expr) => (Async::Err($expr))
-}
-
-/// compose!(future! {
-///     err!(123)
-/// })
-#[macro_export]
-macro_rules! compose {
-    ($expr:expr) => (Async::Err($expr))
-}
-
-#[macro_export]
-macro_rules! async {
-    ($expr:expr) => (match $expr {
-        Result::Ok(val) => val,
-        Result::Err(err) => {
-            use std::convert;
-            return $crate::Async::Err(convert::From::from(err));
-        }
-    })
-}
-
-/// Create a `Future` with a slightly nicer syntax.
-///
-/// ```notrust
-/// future! {
-///     Ok(123)
-/// }
-/// ```
-#[macro_export]
-macro_rules! future {
-    ($x:expr) => {{
-        Future::new(|| {
-            $x
-        })
-    }}
-}
-
-#[derive(Debug)]
-pub enum PromiseState {
-    Waiting,
-    Resolved,
-    Failed
-}
-
-#[derive(Debug)]
-pub struct Promise<T, E=()> {
-    chan: Sender<Async<T, E>>,
-    rx: Option<Receiver<Async<T, E>>>,
-    state: PromiseState
-}
-
-impl<T, E> Promise<T, E>
-    where T: Send + 'static,
-          E: Send + 'static
-{
+    /// Bridge from `std::task::Poll`, with `Pending` mapping to `None`.
+    ///
     /// ```
-    /// use tangle::{Promise};
-    /// let mut p = Promise::<u32, ()>::new();
+    /// use std::task::Poll;
+    /// use tangle::Async;
     ///
-    /// p.future();
+    /// let ready: Poll<Result<u32, ()>> = Poll::Ready(Ok(5));
+    /// assert!(Async::from_poll(ready).unwrap().is_ok());
+    /// assert!(Async::<u32, ()>::from_poll(Poll::Pending).is_none());
     /// ```
-    pub fn new() -> Promise<T, E> {
-        let (tx, rx) = channel::<Async<T, E>>();
-
-        Promise {
-            chan: tx,
-            rx: Some(rx),
-            state: PromiseState::Waiting
+    pub fn from_poll(poll: Poll<Result<T, E>>) -> Option<Async<T, E>> {
+        match poll {
+            Poll::Ready(Ok(t)) => Some(Async::Ok(t)),
+            Poll::Ready(Err(e)) => Some(Async::Err(e)),
+            Poll::Pending => None
         }
     }
 
-    pub fn future(&mut self) -> Future<T, E> {
-        if let Some(rx) = mem::replace(&mut self.rx, None) {
-            return Future::<T, E>::from_async_channel(rx);
-        } else {
-            panic!("Unexpected None");
+    /// The inverse of `from_poll`. A `Continue` has no `Poll` equivalent and panics.
+    pub fn into_poll(self) -> Poll<Result<T, E>> {
+        match self {
+            Async::Ok(t) => Poll::Ready(Ok(t)),
+            Async::Err(e) => Poll::Ready(Err(e)),
+            Async::Continue(_) => panic!("Cannot convert `Async::Continue` into a `Poll`.")
         }
     }
-}
-
-/// A value that will be resolved sometime into the future, asynchronously. `Future`s use
-/// an internal threadpool to handle asynchronous tasks.
-#[derive(Debug)]
-pub struct Future<T, E=()> {
-    receiver: Receiver<Async<T, E>>,
-    read: bool
-}
 
-impl<T, E=()> Future<T, E>
-    where T: Send + 'static,
-          E: Send + 'static
-{
+    /// `Async::Ok` for `Some`, otherwise the error produced by `err_fn`.
+    ///
     /// ```
-    /// use tangle::{Future, Async};
+    /// use tangle::Async;
     ///
-    /// Future::new(|| if true { Async::Ok(123) } else { Async::Err("Foobar") });
+    /// assert!(Async::lift_option(Some(5), || "missing").is_ok());
+    /// assert!(Async::<u32, _>::lift_option(None, || "missing").is_err());
     /// ```
-    pub fn new<F>(f: F) -> Future<T, E>
-        where F: FnOnce() -> Async<T, E> + Send + 'static
+    pub fn lift_option<F>(opt: Option<T>, err_fn: F) -> Async<T, E>
+        where F: FnOnce() -> E
     {
-        let (tx, rx) = channel();
-
-        POOL.lock().unwrap().execute(move || { tx.send(f()); });
-
-        Future::<T, E> {
-            receiver: rx,
-            read: false
+        match opt {
+            Some(t) => Async::Ok(t),
+            None => Async::Err(err_fn())
         }
     }
 
-    pub fn from_async_channel(receiver: Receiver<Async<T, E>>) -> Future<T, E> {
-        Future::<T, E> {
-            receiver: receiver,
-            read: false
+    pub fn lift_result(res: Result<T, E>) -> Async<T, E> {
+        match res {
+            Ok(t) => Async::Ok(t),
+            Err(e) => Async::Err(e)
         }
     }
 
-    pub fn channel() -> (Sender<T>, Future<T, E>) {
-        let (ret_tx, ret_rx) = channel();
-        let (tx, rx) = channel();
-
-        POOL.lock().expect("error acquiring a lock.").execute(move || {
-            match ret_rx.recv() {
-                Ok(v) => { tx.send(Async::Ok(v)).expect("error sending on to the channel.") },
-                Err(err) => { panic!("{:?}", err) }
-            };
-        });
-
-        (ret_tx, Future::<T, E> {
-            receiver: rx,
-            read: false
-        })
+    /// `Async::Ok(ok_val)` when `b` holds, `Async::Err(err_val)` otherwise.
+    pub fn lift_bool(b: bool, ok_val: T, err_val: E) -> Async<T, E> {
+        if b { Async::Ok(ok_val) } else { Async::Err(err_val) }
     }
 
-    /// Create a new future from the receiving end of a native channel.
+    /// The `Ok` value, or `T::default()` on error. Panics on `Continue`.
     ///
     /// ```
-    /// use tangle::{Future, Async};
-    /// use std::thread;
-    /// use std::sync::mpsc::channel;
+    /// use tangle::Async;
     ///
-    /// let (tx, rx) = channel();
-    /// Future::<u32>::from_channel(rx).and_then(|v| {
-    ///     assert_eq!(v, 1235);
-    ///     Async::Ok(())
-    /// });
-    /// tx.send(1235);
+    /// let val: Async<usize, &str> = Async::Err("failed");
+    /// assert_eq!(val.recover_or_default(), 0);
     /// ```
-    pub fn from_channel(receiver: Receiver<T>) -> Future<T, E> {
-        let (tx, rx) = channel();
-
-        POOL.lock().expect("error acquiring a lock.").execute(move || {
-            match receiver.recv() {
-                Ok(v) => { tx.send(Async::Ok(v)).expect("error sending on to the channel.") },
-                Err(err) => { panic!("{:?}", err) }
-            };
-        });
-
-        Future::<T, E> {
-            receiver: rx,
-            read: false
+    pub fn recover_or_default(self) -> T
+        where T: Default
+    {
+        match self {
+            Async::Ok(t) => t,
+            Async::Err(_) => T::default(),
+            Async::Continue(_) => panic!("Cannot recover `Async::Continue`, it has not resolved yet.")
         }
     }
 
-    /// ```
-    /// use tangle::{Future, Async};
+    /// Collect every value, gathering all errors instead of stopping at the first.
+    /// Panics on `Continue`, the values are expected to be resolved already.
     ///
-    /// let f: Future<usize> = Future::unit(1);
+    /// ```
+    /// use tangle::Async;
     ///
-    /// let purchase: Future<String> = f.and_then(|num| Async::Ok(num.to_string()));
+    /// let checks: Vec<Async<u32, &str>> = vec![Async::Ok(1), Async::Err("a"), Async::Err("b")];
     ///
-    /// match purchase.recv() {
-    ///     Ok(val) => assert_eq!(val, "1".to_string()),
+    /// match Async::collect_results(checks) {
+    ///     Async::Err(errors) => assert_eq!(errors, vec!["a", "b"]),
     ///     _ => panic!("unexpected")
     /// }
     /// ```
-    pub fn and_then<F, S>(self, f: F) -> Future<S, E>
-        where F: FnOnce(T) -> Async<S, E> + Send + 'static,
-              S: Send + 'static
+    pub fn collect_results<I>(iter: I) -> Async<Vec<T>, Vec<E>>
+        where I: IntoIterator<Item = Async<T, E>>
     {
-        let (tx, rx) = channel();
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
 
-        POOL.lock().expect("error acquiring a lock.").execute(move || {
-            match self.recv() {
-                Ok(val) => { tx.send(f(val)); },
-                Err(err) => { tx.send(Async::Err(err)); }
+        for val in iter {
+            match val {
+                Async::Ok(t) => values.push(t),
+                Async::Err(e) => errors.push(e),
+                Async::Continue(_) => panic!("Cannot collect `Async::Continue`, it has not resolved yet.")
             }
-        });
-
-        Future::<S, E> {
-            receiver: rx,
-            read: false
         }
+
+        if errors.is_empty() { Async::Ok(values) } else { Async::Err(errors) }
+    }
+
+    /// Throw away the success value.
+    pub fn as_unit(self) -> Async<(), E>
+        where T: Send + 'static,
+              E: Send + 'static
+    {
+        self.map(|_| ())
     }
 
+    /// Wrap this value in a future, the inverse of `Future::await`. A `Continue`
+    /// hands back its inner future as is.
+    ///
     /// ```
-    /// use tangle::{Future, Async};
+    /// use tangle::Async;
     ///
-    /// let f: Future<usize> = Future::unit(1);
+    /// let val: Async<u32, ()> = Async::Ok(5);
+    /// assert_eq!(val.to_future().map(|n| n + 1).recv(), Ok(6));
+    /// ```
+    pub fn to_future(self) -> Future<T, E>
+        where T: Send + 'static,
+              E: Send + 'static
+    {
+        match self {
+            Async::Ok(t) => Future::unit(t),
+            Async::Err(e) => Future::err(e),
+            Async::Continue(f) => f
+        }
+    }
+
+    /// Map over the future a `Continue` holds, the counterpart of `map` for values
+    /// that haven't resolved yet. `Ok` and `Err` are turned into a resolved future
+    /// first, so `f` sees every variant and the result is always a `Continue`.
     ///
-    /// let purchase: Future<String> = f.map(|num| num.to_string());
+    /// ```
+    /// use tangle::{Async, Future};
     ///
-    /// match purchase.recv() {
-    ///     Ok(val) => assert_eq!(val, "1".to_string()),
-    ///     _ => panic!("unexpected")
-    /// }
+    /// let val: Async<u32, ()> = Async::Continue(Future::unit(2));
+    /// let val = val.map_future(|f| f.map(|n| n * 10));
+    ///
+    /// assert_eq!(val.to_future().recv(), Ok(20));
     /// ```
-    pub fn map<F, S>(self, f: F) -> Future<S, E>
-        where F: FnOnce(T) -> S + Send + 'static,
-              S: Send + 'static
+    pub fn map_future<U, F>(self, f: F) -> Async<U, E>
+        where F: FnOnce(Future<T, E>) -> Future<U, E>,
+              T: Send + 'static,
+              E: Send + 'static
     {
-        let (tx, rx) = channel();
+        Async::Continue(f(self.to_future()))
+    }
 
-        POOL.lock().expect("error acquiring a lock.").execute(move || {
-            match self.recv() {
-                Ok(val) => { tx.send(Async::Ok(f(val))); },
-                Err(err) => { tx.send(Async::Err(err)); }
+    /// Apply the function in `ff` to the value in `fa`. The first error, going from
+    /// `ff` to `fa`, wins, and a `Continue` on either side defers the whole thing to
+    /// a future.
+    ///
+    /// ```
+    /// use tangle::Async;
+    ///
+    /// let ff: Async<fn(u32) -> u32, ()> = Async::Ok(|x| x + 1);
+    /// assert_eq!(Async::ap(ff, Async::Ok(5)).unwrap(), 6);
+    /// ```
+    pub fn ap<U, F>(ff: Async<F, E>, fa: Async<T, E>) -> Async<U, E>
+        where F: FnOnce(T) -> U + Send + 'static,
+              T: Send + 'static,
+              U: Send + 'static,
+              E: Send + 'static
+    {
+        match (ff, fa) {
+            (Async::Err(e), _) => Async::Err(e),
+            (Async::Ok(f), Async::Ok(t)) => Async::Ok(f(t)),
+            (Async::Ok(_), Async::Err(e)) => Async::Err(e),
+            (Async::Ok(f), Async::Continue(ft)) => Async::Continue(ft.map(f)),
+            (Async::Continue(ff), Async::Ok(t)) => Async::Continue(ff.map(move |f| f(t))),
+            (Async::Continue(ff), Async::Err(e)) => Async::Continue(ff.and_then(move |_| Async::Err(e))),
+            (Async::Continue(ff), Async::Continue(ft)) => {
+                Async::Continue(ff.and_then(move |f| Async::Continue(ft.map(f))))
             }
-        });
-
-        Future::<S, E> {
-            receiver: rx,
-            read: false
         }
     }
 
-    pub fn recv(self) -> Result<T, E> {
-        let val = self.receiver.recv().expect("error trying to wait for channel.");
-
-        match val {
-            Async::Ok(val) => Ok(val),
-            Async::Err(err) => Err(err),
-            Continue(f) => f.recv()
-        }
+    /// Convert the error with `From`, same as `Future::coerce_err`.
+    pub fn coerce_err<F2>(self) -> Async<T, F2>
+        where F2: From<E> + Send + 'static,
+              T: Send + 'static,
+              E: Send + 'static
+    {
+        self.map_err(F2::from)
     }
 
-    /// Wrap a value into a `Future` that completes right away.
-    ///
-    /// ## Usage
+    /// All the values if every one is `Ok`, otherwise the first error. Panics on
+    /// `Continue`, this is for values that are resolved already, `Future::join_all`
+    /// covers the rest.
     ///
     /// ```
-    /// use tangle::Future;
+    /// use tangle::Async;
     ///
-    /// let _: Future<usize> = Future::unit(5);
+    /// let checks: Vec<Async<u32, &str>> = vec![Async::Ok(1), Async::Err("a"), Async::Err("b")];
+    ///
+    /// match Async::short_circuit(checks) {
+    ///     Async::Err(err) => assert_eq!(err, "a"),
+    ///     _ => panic!("unexpected")
+    /// }
     /// ```
-    pub fn unit(val: T) -> Future<T, E> {
-        let (tx, rx) = channel();
-
-        tx.send(Async::Ok(val));
+    pub fn short_circuit(results: Vec<Async<T, E>>) -> Async<Vec<T>, E> {
+        let mut values = Vec::with_capacity(results.len());
 
-        Future::<T, E> {
-            receiver: rx,
-            read: false
+        for val in results {
+            match val {
+                Async::Ok(t) => values.push(t),
+                Async::Err(e) => return Async::Err(e),
+                Async::Continue(_) => panic!("Cannot short circuit `Async::Continue`, it has not resolved yet.")
+            }
         }
+
+        Async::Ok(values)
+    }
+
+    /// Same as `lift_result`.
+    pub fn from_result(res: Result<T, E>) -> Async<T, E> {
+        Async::lift_result(res)
     }
 
+    /// The resolved value as a `Result`. Panics on `Continue`, use `TryFrom` to get
+    /// an `AsyncConversionError` instead.
+    ///
     /// ```
-    /// use tangle::Future;
+    /// use tangle::Async;
     ///
-    /// let _: Future<usize, &str> = Future::err("foobar");
+    /// let val: Async<u32, &str> = Async::Err("failed");
+    /// assert_eq!(val.into_result(), Err("failed"));
     /// ```
-    pub fn err(err: E) -> Future<T, E> {
-        let (tx, rx) = channel();
-
-        tx.send(Async::Err(err));
-
+    pub fn into_result(self) -> Result<T, E> {
+        match self {
+            Async::Ok(t) => Ok(t),
+            Async::Err(e) => Err(e),
+            Async::Continue(_) => panic!("Cannot convert `Async::Continue` into a `Result`, it has not resolved yet.")
+        }
+    }
+}
+
+impl<T, E> Async<Option<T>, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// Swap the `Option` to the outside, `Async::Ok(None)` becoming `None`. A
+    /// `Continue` can't be inspected without waiting, so it's always `Some`, and its
+    /// future panics if it turns out to hold `None`.
+    ///
+    /// ```
+    /// use tangle::Async;
+    ///
+    /// let val: Async<Option<u32>, ()> = Async::Ok(None);
+    /// assert!(val.transpose().is_none());
+    /// ```
+    pub fn transpose(self) -> Option<Async<T, E>> {
+        match self {
+            Async::Ok(Some(t)) => Some(Async::Ok(t)),
+            Async::Ok(None) => None,
+            Async::Err(e) => Some(Async::Err(e)),
+            Async::Continue(f) => Some(Async::Continue(f.map(|opt| {
+                opt.expect("transposed `Async::Continue` resolved to `None`.")
+            })))
+        }
+    }
+}
+
+impl<T, E> Async<Async<T, E>, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// Remove one level of nesting, so `map` followed by `flatten` is the same as
+    /// `and_then`. An inner `Continue` stays a `Continue`, its future hasn't
+    /// resolved yet.
+    ///
+    /// ```
+    /// use tangle::Async;
+    ///
+    /// let val: Async<Async<u32, &str>, &str> = Async::Ok(Async::Err("failed"));
+    /// assert_eq!(val.flatten().unwrap_err(), "failed");
+    /// ```
+    pub fn flatten(self) -> Async<T, E> {
+        match self {
+            Async::Ok(inner) => inner,
+            Async::Err(e) => Async::Err(e),
+            Async::Continue(f) => Async::Continue(f.and_then(|inner| inner))
+        }
+    }
+}
+
+/// Same as `Async::collect_results`.
+impl<T, E> iter::FromIterator<Async<T, E>> for Async<Vec<T>, Vec<E>> {
+    fn from_iter<I>(iter: I) -> Async<Vec<T>, Vec<E>>
+        where I: IntoIterator<Item = Async<T, E>>
+    {
+        Async::collect_results(iter)
+    }
+}
+
+impl<T, E> From<Result<T, E>> for Async<T, E> {
+    fn from(res: Result<T, E>) -> Async<T, E> {
+        Async::lift_result(res)
+    }
+}
+
+/// Only resolved values can be cloned, a `Continue` holds a future that can't be
+/// and panics.
+impl<T, E> Clone for Async<T, E>
+    where T: Clone,
+          E: Clone
+{
+    fn clone(&self) -> Async<T, E> {
+        match *self {
+            Async::Ok(ref t) => Async::Ok(t.clone()),
+            Async::Err(ref e) => Async::Err(e.clone()),
+            Async::Continue(_) => panic!("Cannot clone `Async::Continue`.")
+        }
+    }
+}
+
+/// A `Continue` is never equal to anything, itself included, since its value isn't
+/// known yet.
+///
+/// ```
+/// use tangle::{Async, Future};
+///
+/// assert_eq!(Async::Ok::<u32, ()>(1), Async::Ok(1));
+/// assert_ne!(Async::Continue(Future::<u32>::unit(1)), Async::Ok(1));
+/// ```
+impl<T, E> PartialEq for Async<T, E>
+    where T: PartialEq,
+          E: PartialEq
+{
+    fn eq(&self, other: &Async<T, E>) -> bool {
+        match (self, other) {
+            (Async::Ok(a), Async::Ok(b)) => a == b,
+            (Async::Err(a), Async::Err(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
+/// ```
+/// use tangle::Async;
+///
+/// let val: Async<u32, &str> = Async::Err("disk full");
+/// assert_eq!(val.to_string(), "Async::Err(disk full)");
+/// ```
+impl<T, E> fmt::Display for Async<T, E>
+    where T: fmt::Debug,
+          E: fmt::Display
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Async::Ok(ref t) => write!(f, "Async::Ok({:?})", t),
+            Async::Err(ref e) => write!(f, "Async::Err({})", e),
+            Async::Continue(_) => write!(f, "Async::Continue(..)")
+        }
+    }
+}
+
+/// Wraps the error of an `Async` so it can take part in `?` and error chains that
+/// expect a `std::error::Error`. The wrapped error is reported as the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsyncError<E>(pub E);
+
+impl<E> fmt::Display for AsyncError<E>
+    where E: fmt::Display
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "future failed: {}", self.0)
+    }
+}
+
+impl<E> error::Error for AsyncError<E>
+    where E: error::Error + 'static
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Fails for `Continue`, which has no result yet.
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use tangle::{Async, Future};
+///
+/// let val: Async<u32, ()> = Async::Continue(Future::unit(1));
+/// assert!(Result::try_from(val).is_err());
+/// ```
+impl<T, E> convert::TryFrom<Async<T, E>> for Result<T, E> {
+    type Error = AsyncConversionError<T, E>;
+
+    fn try_from(val: Async<T, E>) -> Result<Result<T, E>, AsyncConversionError<T, E>> {
+        match val {
+            Async::Ok(t) => Ok(Ok(t)),
+            Async::Err(e) => Ok(Err(e)),
+            Async::Continue(future) => Err(AsyncConversionError { future })
+        }
+    }
+}
+
+/// An `Async::Continue` that couldn't be turned into a `Result`, holding on to its
+/// future so it can still be waited on.
+#[derive(Debug)]
+pub struct AsyncConversionError<T, E> {
+    pub future: Future<T, E>
+}
+
+impl<T, E> fmt::Display for AsyncConversionError<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`Async::Continue` has not resolved yet")
+    }
+}
+
+/// Error type of futures that can't fail.
+pub type Never = convert::Infallible;
+
+/// ok!(123)
+#[macro_export]
+macro_rules! ok {
+    ($expr:expr) => (Async::Ok($expr))
+}
+
+// err!(123)
+#[macro_export]
+macro_rules! err {
+    ($expr:expr) => (Async::Err($expr))
+}
+
+/// Hand a future back as an `Async::Continue`, so it becomes the next step of the
+/// chain. Takes either an expression or a block whose last expression is the future.
+///
+/// ```notrust
+/// compose! {
+///     let id = 5;
+///     Future::new(move || Async::Ok(id))
+/// }
+/// ```
+#[macro_export]
+macro_rules! compose {
+    ($expr:expr) => ($crate::Async::Continue($expr));
+    ($($body:tt)*) => ($crate::Async::Continue({ $($body)* }))
+}
+
+#[macro_export]
+macro_rules! async {
+    ($expr:expr) => (match $expr {
+        Result::Ok(val) => val,
+        Result::Err(err) => {
+            use std::convert;
+            return $crate::Async::Err(convert::From::from(err));
+        }
+    })
+}
+
+/// Create a `Future` with a slightly nicer syntax.
+///
+/// ```notrust
+/// future! {
+///     Ok(123)
+/// }
+/// ```
+#[macro_export]
+macro_rules! future {
+    ($x:expr) => {{
+        Future::new(|| {
+            $x
+        })
+    }}
+}
+
+/// Same as `future!`, except `?` works inside the body. An `Err` returns early
+/// with `Async::Err`, converted with `From` like the `async!` macro does.
+///
+/// ```
+/// #[macro_use]
+/// extern crate tangle;
+///
+/// use tangle::{Async, Future};
+///
+/// fn main() {
+///     let f: Future<u32, String> = try_future! {
+///         let n: u32 = "41".parse().map_err(|_| "not a number")?;
+///         Async::Ok(n + 1)
+///     };
+///
+///     assert_eq!(f.recv(), Ok(42));
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_future {
+    ($($body:tt)*) => {{
+        $crate::Future::new(move || {
+            let body = move || -> Result<_, _> { Ok({ $($body)* }) };
+
+            match body() {
+                Result::Ok(val) => val,
+                Result::Err(err) => $crate::Async::Err(err)
+            }
+        })
+    }}
+}
+
+/// Wait for two to six futures at once, resolving with a tuple of their values or
+/// the first error in argument order. The futures must share an error type. Every
+/// argument is evaluated, and so running, before anything is waited on.
+///
+/// ```
+/// #[macro_use]
+/// extern crate tangle;
+///
+/// use tangle::{Async, Future};
+///
+/// fn main() {
+///     let user: Future<&str> = Future::new(|| Async::Ok("ada"));
+///     let count: Future<u32> = Future::new(|| Async::Ok(3));
+///
+///     assert_eq!(join!(user, count), Async::Ok(("ada", 3)));
+/// }
+/// ```
+#[macro_export]
+macro_rules! join {
+    ($a:expr, $b:expr $(,)*) => (
+        $crate::Async::lift_result($a.zip($b).recv())
+    );
+    ($a:expr, $b:expr, $c:expr $(,)*) => (
+        $crate::Async::lift_result($a.zip($b).zip($c).map(|((a, b), c)| (a, b, c)).recv())
+    );
+    ($a:expr, $b:expr, $c:expr, $d:expr $(,)*) => (
+        $crate::Async::lift_result($a.zip($b).zip($c).zip($d).map(|(((a, b), c), d)| (a, b, c, d)).recv())
+    );
+    ($a:expr, $b:expr, $c:expr, $d:expr, $e:expr $(,)*) => (
+        $crate::Async::lift_result($a.zip($b).zip($c).zip($d).zip($e)
+            .map(|((((a, b), c), d), e)| (a, b, c, d, e)).recv())
+    );
+    ($a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr $(,)*) => (
+        $crate::Async::lift_result($a.zip($b).zip($c).zip($d).zip($e).zip($f)
+            .map(|(((((a, b), c), d), e), f)| (a, b, c, d, e, f)).recv())
+    );
+}
+
+/// Wait for the first of two to four futures to resolve, success or not, telling
+/// which one it was with a `RaceResult`. The futures must share an error type and
+/// the others are left to finish on their own.
+///
+/// ```
+/// #[macro_use]
+/// extern crate tangle;
+///
+/// use tangle::{Async, Future, Promise, RaceResult};
+///
+/// fn main() {
+///     let mut never = Promise::<u32, ()>::new();
+///     let cached: Future<&str> = Future::unit("cached");
+///
+///     match race!(never.future(), cached) {
+///         Async::Ok(RaceResult::Second(val)) => assert_eq!(val, "cached"),
+///         _ => panic!("unexpected")
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! race {
+    ($a:expr, $b:expr $(,)*) => (
+        $crate::Async::lift_result($a.select($b).map(|val| match val {
+            $crate::Either::Left(a) => $crate::RaceResult::<_, _, $crate::Never, $crate::Never>::First(a),
+            $crate::Either::Right(b) => $crate::RaceResult::Second(b)
+        }).recv())
+    );
+    ($a:expr, $b:expr, $c:expr $(,)*) => (
+        $crate::Async::lift_result($a.select($b).select($c).map(|val| match val {
+            $crate::Either::Left($crate::Either::Left(a)) => $crate::RaceResult::<_, _, _, $crate::Never>::First(a),
+            $crate::Either::Left($crate::Either::Right(b)) => $crate::RaceResult::Second(b),
+            $crate::Either::Right(c) => $crate::RaceResult::Third(c)
+        }).recv())
+    );
+    ($a:expr, $b:expr, $c:expr, $d:expr $(,)*) => (
+        $crate::Async::lift_result($a.select($b).select($c).select($d).map(|val| match val {
+            $crate::Either::Left($crate::Either::Left($crate::Either::Left(a))) => $crate::RaceResult::First(a),
+            $crate::Either::Left($crate::Either::Left($crate::Either::Right(b))) => $crate::RaceResult::Second(b),
+            $crate::Either::Left($crate::Either::Right(c)) => $crate::RaceResult::Third(c),
+            $crate::Either::Right(d) => $crate::RaceResult::Fourth(d)
+        }).recv())
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromiseState {
+    Waiting,
+    Resolved,
+    Failed
+}
+
+pub struct Promise<T, E=()> {
+    // Hands the value on, returning whether anyone was left to receive it.
+    chan: Box<dyn FnMut(Async<T, E>) -> bool + Send>,
+    rx: Option<oneshot::Receiver<Async<T, E>>>,
+    // Set once `future` is called, for a mapped promise to know where to send.
+    taken: Option<Arc<AtomicBool>>,
+    state: PromiseState,
+    on_cancel: Option<Box<dyn FnOnce() + Send>>
+}
+
+impl<T, E> Promise<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// ```
+    /// use tangle::{Promise};
+    /// let mut p = Promise::<u32, ()>::new();
+    ///
+    /// p.future();
+    /// ```
+    pub fn new() -> Promise<T, E> {
+        let (tx, rx) = oneshot::channel::<Async<T, E>>();
+
+        Promise {
+            chan: Box::new(move |val| tx.send(val).is_ok()),
+            rx: Some(rx),
+            taken: None,
+            state: PromiseState::Waiting,
+            on_cancel: None
+        }
+    }
+
+    pub fn future(&mut self) -> Future<T, E> {
+        if let Some(rx) = self.rx.take() {
+            if let Some(ref taken) = self.taken {
+                taken.store(true, Ordering::SeqCst);
+            }
+
+            Future::<T, E>::from_oneshot(rx)
+        } else {
+            panic!("Unexpected None");
+        }
+    }
+
+    /// Fulfill the promise, handing `val` to the future on the other end. Panics if
+    /// the promise was already resolved or rejected.
+    ///
+    /// ```
+    /// use tangle::Promise;
+    ///
+    /// let mut p = Promise::<u32>::new();
+    /// let f = p.future();
+    ///
+    /// p.resolve(5);
+    /// assert_eq!(f.recv().unwrap(), 5);
+    /// ```
+    pub fn resolve(&mut self, val: T) {
+        self.try_resolve(val).expect("cannot resolve the promise");
+    }
+
+    /// Fail the promise, handing `err` to the future on the other end. Panics if
+    /// the promise was already resolved or rejected.
+    ///
+    /// ```
+    /// use tangle::Promise;
+    ///
+    /// let mut p = Promise::<u32, &str>::new();
+    /// let f = p.future();
+    ///
+    /// p.reject("failed");
+    /// assert_eq!(f.recv(), Err("failed"));
+    /// ```
+    pub fn reject(&mut self, err: E) {
+        self.try_reject(err).expect("cannot reject the promise");
+    }
+
+    /// Same as `resolve`, but returns an error instead of panicking.
+    ///
+    /// ```
+    /// use tangle::Promise;
+    ///
+    /// let mut p = Promise::<u32>::new();
+    ///
+    /// assert!(p.try_resolve(1).is_ok());
+    /// assert!(p.try_resolve(2).is_err());
+    /// ```
+    pub fn try_resolve(&mut self, val: T) -> Result<(), AlreadyResolvedError> {
+        self.settle(Async::Ok(val), PromiseState::Resolved)
+    }
+
+    /// Same as `reject`, but returns an error instead of panicking.
+    pub fn try_reject(&mut self, err: E) -> Result<(), AlreadyResolvedError> {
+        self.settle(Async::Err(err), PromiseState::Failed)
+    }
+
+    /// Register `f` to run if the promise gets resolved after its future was
+    /// dropped, so whoever resolves it can clean up after the abandoned work.
+    ///
+    /// ```
+    /// use std::sync::mpsc::channel;
+    /// use tangle::Promise;
+    ///
+    /// let (tx, rx) = channel();
+    /// let mut p = Promise::<u32>::new();
+    ///
+    /// p.when_cancelled(move || tx.send(()).unwrap());
+    /// drop(p.future());
+    /// p.resolve(5);
+    ///
+    /// assert!(rx.try_recv().is_ok());
+    /// ```
+    pub fn when_cancelled<F>(&mut self, f: F)
+        where F: FnOnce() + Send + 'static
+    {
+        self.on_cancel = Some(Box::new(f));
+    }
+
+    /// Adapt the promise to be fulfilled with a `U` instead of a `T`. Whatever the
+    /// returned promise is resolved with goes through `f`, right there on the
+    /// resolving thread, before reaching the original future, so take that future
+    /// before mapping. If the future of the returned promise is taken instead, the
+    /// value goes to it as is and `f` isn't called. A promise that was already
+    /// resolved or rejected stays that way, resolving the mapped one fails too.
+    ///
+    /// ```
+    /// use tangle::Promise;
+    ///
+    /// let mut p = Promise::<usize>::new();
+    /// let f = p.future();
+    ///
+    /// let mut p = p.map_before_resolve(|s: String| s.len());
+    /// p.resolve("hello".to_string());
+    ///
+    /// assert_eq!(f.recv().unwrap(), 5);
+    /// ```
+    pub fn map_before_resolve<U, F>(self, f: F) -> Promise<U, E>
+        where F: FnOnce(U) -> T + Send + 'static,
+              U: Send + 'static
+    {
+        let (tx, rx) = oneshot::channel::<Async<U, E>>();
+        let taken = Arc::new(AtomicBool::new(false));
+        let forward = taken.clone();
+        let mut chan = self.chan;
+        // Only ever taken once, the mapped promise settles at most once.
+        let mut f = Some(f);
+
+        Promise {
+            chan: Box::new(move |val| {
+                if forward.load(Ordering::SeqCst) {
+                    tx.send(val).is_ok()
+                } else {
+                    chan(val.map(f.take().expect("promise settled twice")))
+                }
+            }),
+            rx: Some(rx),
+            taken: Some(taken),
+            state: self.state,
+            on_cancel: self.on_cancel
+        }
+    }
+
+    /// Take the futures of several promises at once. Each promise becomes a
+    /// `Resolver` that can be handed to whoever will fulfill it, while the combined
+    /// future resolves once all of them are, or with the first rejection.
+    ///
+    /// ```
+    /// use tangle::Promise;
+    ///
+    /// let (resolvers, all) = Promise::<u32>::all(vec![Promise::new(), Promise::new()]);
+    ///
+    /// for (i, resolver) in resolvers.into_iter().enumerate() {
+    ///     resolver.resolve(i as u32);
+    /// }
+    ///
+    /// assert_eq!(all.recv(), Ok(vec![0, 1]));
+    /// ```
+    pub fn all(promises: Vec<Promise<T, E>>) -> (Vec<Resolver<T, E>>, Future<Vec<T>, E>) {
+        let mut futures = Vec::with_capacity(promises.len());
+        let mut resolvers = Vec::with_capacity(promises.len());
+
+        for mut promise in promises {
+            futures.push(promise.future());
+            resolvers.push(Resolver { promise });
+        }
+
+        (resolvers, Future::join_all(futures))
+    }
+
+    fn settle(&mut self, val: Async<T, E>, state: PromiseState) -> Result<(), AlreadyResolvedError> {
+        if self.state != PromiseState::Waiting {
+            return Err(AlreadyResolvedError);
+        }
+
+        self.state = state;
+
+        if !(self.chan)(val) {
+            if let Some(f) = self.on_cancel.take() {
+                f();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, E> fmt::Debug for Promise<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Promise")
+            .field("state", &self.state)
+            .field("on_cancel", &self.on_cancel.is_some())
+            .finish()
+    }
+}
+
+impl<T, E> Default for Promise<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    fn default() -> Promise<T, E> {
+        Promise::new()
+    }
+}
+
+/// A promise with any number of futures. Each one gets its own clone of the value,
+/// including futures taken after the promise was settled.
+pub struct BroadcastPromise<T, E=()> {
+    chans: Vec<oneshot::Sender<Async<T, E>>>,
+    settled: Option<Result<T, E>>
+}
+
+impl<T, E> BroadcastPromise<T, E>
+    where T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    /// ```
+    /// use tangle::BroadcastPromise;
+    ///
+    /// let mut p = BroadcastPromise::<u32>::new();
+    /// let a = p.future();
+    /// let b = p.future();
+    ///
+    /// p.resolve(5);
+    /// assert_eq!(a.recv(), Ok(5));
+    /// assert_eq!(b.recv(), Ok(5));
+    /// ```
+    pub fn new() -> BroadcastPromise<T, E> {
+        BroadcastPromise { chans: Vec::new(), settled: None }
+    }
+
+    pub fn future(&mut self) -> Future<T, E> {
+        match self.settled {
+            Some(Ok(ref val)) => Future::unit(val.clone()),
+            Some(Err(ref err)) => Future::err(err.clone()),
+            None => {
+                let (tx, rx) = oneshot::channel();
+                self.chans.push(tx);
+
+                Future::from_oneshot(rx)
+            }
+        }
+    }
+
+    /// Fulfill every future with a clone of `val`. Panics if the promise was
+    /// already resolved or rejected.
+    pub fn resolve(&mut self, val: T) {
+        self.try_resolve(val).expect("cannot resolve the promise");
+    }
+
+    /// Fail every future with a clone of `err`. Panics if the promise was already
+    /// resolved or rejected.
+    pub fn reject(&mut self, err: E) {
+        self.try_reject(err).expect("cannot reject the promise");
+    }
+
+    pub fn try_resolve(&mut self, val: T) -> Result<(), AlreadyResolvedError> {
+        self.settle(Ok(val))
+    }
+
+    pub fn try_reject(&mut self, err: E) -> Result<(), AlreadyResolvedError> {
+        self.settle(Err(err))
+    }
+
+    pub fn state(&self) -> PromiseState {
+        match self.settled {
+            None => PromiseState::Waiting,
+            Some(Ok(_)) => PromiseState::Resolved,
+            Some(Err(_)) => PromiseState::Failed
+        }
+    }
+
+    fn settle(&mut self, val: Result<T, E>) -> Result<(), AlreadyResolvedError> {
+        if self.settled.is_some() {
+            return Err(AlreadyResolvedError);
+        }
+
+        // Futures that were dropped in the meantime just miss out.
+        for chan in self.chans.drain(..) {
+            let _ = chan.send(Async::lift_result(val.clone()));
+        }
+
+        self.settled = Some(val);
+
+        Ok(())
+    }
+}
+
+impl<T, E> fmt::Debug for BroadcastPromise<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BroadcastPromise")
+            .field("futures", &self.chans.len())
+            .field("settled", &self.settled.is_some())
+            .finish()
+    }
+}
+
+impl<T, E> Default for BroadcastPromise<T, E>
+    where T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    fn default() -> BroadcastPromise<T, E> {
+        BroadcastPromise::new()
+    }
+}
+
+/// Returned when resolving or rejecting a promise that was already settled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlreadyResolvedError;
+
+impl fmt::Display for AlreadyResolvedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "promise was already resolved or rejected")
+    }
+}
+
+/// The fulfilling half of a promise whose future has already been handed out.
+#[derive(Debug)]
+pub struct Resolver<T, E=()> {
+    promise: Promise<T, E>
+}
+
+impl<T, E> Resolver<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    pub fn resolve(mut self, val: T) {
+        self.promise.resolve(val);
+    }
+
+    pub fn reject(mut self, err: E) {
+        self.promise.reject(err);
+    }
+}
+
+/// A value that will be resolved sometime into the future, asynchronously. `Future`s use
+/// an internal threadpool to handle asynchronous tasks.
+///
+/// Dropping a future doesn't stop its work, which still runs on the pool, the value
+/// is just dropped once nobody is left to receive it.
+#[derive(Debug)]
+pub struct Future<T, E=()> {
+    inner: FutureInner<T, E>,
+    executor: Option<FutureExecutor>,
+    waker: Option<Arc<Mutex<Waker>>>
+}
+
+/// Where a future's value comes from. Futures that are resolved from the start hold
+/// on to their value directly instead of going through a channel. That value is a
+/// plain `Result`, an `Async` could hold a future holding itself. `Never` is for
+/// futures known never to resolve, which nothing has to wait on.
+#[derive(Debug)]
+enum FutureInner<T, E> {
+    Ready(Result<T, E>),
+    Channel(oneshot::Receiver<Async<T, E>>),
+    Never
+}
+
+impl<T, E> FutureInner<T, E> {
+    fn recv(self) -> Async<T, E> {
+        match self {
+            FutureInner::Ready(val) => Async::lift_result(val),
+            FutureInner::Channel(rx) => rx.recv().expect("error trying to wait for channel."),
+            FutureInner::Never => park_forever()
+        }
+    }
+}
+
+/// Run `task` on `executor`, or the global pool when there's none.
+fn execute<F>(executor: &Option<FutureExecutor>, task: F)
+    where F: FnOnce() + Send + 'static
+{
+    match *executor {
+        Some(ref executor) => executor.execute(task),
+        None => global_pool().execute(task)
+    }
+}
+
+/// Sets the flag when dropped.
+struct SetOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Block the calling thread for good, for waiting on a future that never resolves.
+fn park_forever() -> ! {
+    loop {
+        thread::park();
+    }
+}
+
+impl<T, E> Future<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// Future::new(|| if true { Async::Ok(123) } else { Async::Err("Foobar") });
+    /// ```
+    pub fn new<F>(f: F) -> Future<T, E>
+        where F: FnOnce() -> Async<T, E> + Send + 'static
+    {
+        Future::spawn(None, f)
+    }
+
+    /// Like `new`, but `f` runs on `executor` instead of the global pool, and so do
+    /// the combinators chained onto the future.
+    pub fn new_on<F>(executor: &FutureExecutor, f: F) -> Future<T, E>
+        where F: FnOnce() -> Async<T, E> + Send + 'static
+    {
+        Future::spawn(Some(executor.clone()), f)
+    }
+
+    /// Like `new`, but `f` runs on a dedicated thread with a `bytes` sized stack
+    /// instead of the pool. Meant for deeply recursive work that would overflow the
+    /// stack of a pool thread. A future's computation starts as soon as it's created,
+    /// which is why this is a constructor rather than a combinator.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// fn depth(n: u64) -> u64 { if n == 0 { 0 } else { 1 + depth(n - 1) } }
+    ///
+    /// let f: Future<u64> = Future::with_stack_size(64 * 1024 * 1024, || Async::Ok(depth(100_000)));
+    /// assert_eq!(f.recv(), Ok(100_000));
+    /// ```
+    pub fn with_stack_size<F>(bytes: usize, f: F) -> Future<T, E>
+        where F: FnOnce() -> Async<T, E> + Send + 'static
+    {
+        let (tx, rx) = oneshot::channel();
+
+        thread::Builder::new().stack_size(bytes).spawn(move || {
+            let _ = tx.send(f());
+        }).expect("error spawning a thread.");
+
+        Future::from_oneshot(rx)
+    }
+
+    /// Run `f` on the pool once `dur` has passed. The wait happens on a timer thread
+    /// of its own, so neither the caller nor a pool thread sits idle in the meantime.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use tangle::{Future, Async};
+    ///
+    /// let start = Instant::now();
+    /// let f: Future<u32> = Future::delayed(Duration::from_millis(10), || Async::Ok(5));
+    ///
+    /// assert_eq!(f.recv(), Ok(5));
+    /// assert!(start.elapsed() >= Duration::from_millis(10));
+    /// ```
+    pub fn delayed<F>(dur: Duration, f: F) -> Future<T, E>
+        where F: FnOnce() -> Async<T, E> + Send + 'static
+    {
+        let (tx, rx) = oneshot::channel();
+
+        thread::spawn(move || {
+            thread::sleep(dur);
+            global_pool().execute(move || { let _ = tx.send(f()); });
+        });
+
+        Future::from_oneshot(rx)
+    }
+
+    pub fn from_async_channel(receiver: Receiver<Async<T, E>>) -> Future<T, E> {
+        Future::<T, E> {
+            inner: FutureInner::Channel(receiver.into()),
+            executor: None,
+            waker: None
+        }
+    }
+
+    pub fn channel() -> (Sender<T>, Future<T, E>) {
+        let (ret_tx, ret_rx) = channel();
+        let (tx, rx) = oneshot::channel();
+
+        global_pool().execute(move || {
+            match ret_rx.recv() {
+                Ok(v) => { let _ = tx.send(Async::Ok(v)); },
+                Err(err) => { panic!("{:?}", err) }
+            };
+        });
+
+        (ret_tx, Future::<T, E> {
+            inner: FutureInner::Channel(rx),
+            executor: None,
+            waker: None
+        })
+    }
+
+    /// Create a new future from the receiving end of a native channel.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    /// use std::thread;
+    /// use std::sync::mpsc::channel;
+    ///
+    /// let (tx, rx) = channel();
+    /// Future::<u32>::from_channel(rx).and_then(|v| {
+    ///     assert_eq!(v, 1235);
+    ///     Async::Ok(())
+    /// });
+    /// tx.send(1235);
+    /// ```
+    pub fn from_channel(receiver: Receiver<T>) -> Future<T, E> {
+        let (tx, rx) = oneshot::channel();
+
+        global_pool().execute(move || {
+            match receiver.recv() {
+                Ok(v) => { let _ = tx.send(Async::Ok(v)); },
+                Err(err) => { panic!("{:?}", err) }
+            };
+        });
+
+        Future::<T, E> {
+            inner: FutureInner::Channel(rx),
+            executor: None,
+            waker: None
+        }
+    }
+
+    /// Same as `from_channel`, except the future fails with `err` if the sender is
+    /// dropped without sending anything, rather than panicking.
+    ///
+    /// ```
+    /// use std::sync::mpsc::channel;
+    /// use tangle::Future;
+    ///
+    /// let (tx, rx) = channel::<u32>();
+    /// drop(tx);
+    ///
+    /// assert_eq!(Future::from_channel_with_err(rx, "sender dropped").recv(), Err("sender dropped"));
+    /// ```
+    pub fn from_channel_with_err(receiver: Receiver<T>, err: E) -> Future<T, E> {
+        let (tx, rx) = oneshot::channel();
+
+        // The sender lives outside of the pool, so wait for it on a thread of its own.
+        thread::spawn(move || {
+            let _ = tx.send(match receiver.recv() {
+                Ok(val) => Async::Ok(val),
+                Err(_) => Async::Err(err)
+            });
+        });
+
+        Future::from_oneshot(rx)
+    }
+
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<usize> = Future::unit(1);
+    ///
+    /// let purchase: Future<String> = f.and_then(|num| Async::Ok(num.to_string()));
+    ///
+    /// match purchase.recv() {
+    ///     Ok(val) => assert_eq!(val, "1".to_string()),
+    ///     _ => panic!("unexpected")
+    /// }
+    /// ```
+    pub fn and_then<F, S>(self, f: F) -> Future<S, E>
+        where F: FnOnce(T) -> Async<S, E> + Send + 'static,
+              S: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => f(val),
+            Err(err) => Async::Err(err)
+        })
+    }
+
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<usize> = Future::unit(1);
+    ///
+    /// let purchase: Future<String> = f.map(|num| num.to_string());
+    ///
+    /// match purchase.recv() {
+    ///     Ok(val) => assert_eq!(val, "1".to_string()),
+    ///     _ => panic!("unexpected")
+    /// }
+    /// ```
+    pub fn map<F, S>(self, f: F) -> Future<S, E>
+        where F: FnOnce(T) -> S + Send + 'static,
+              S: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => Async::Ok(f(val)),
+            Err(err) => Async::Err(err)
+        })
+    }
+
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, u32> = Future::err(404);
+    /// let f: Future<u32, String> = f.map_err(|code| format!("status {}", code));
+    ///
+    /// assert_eq!(f.recv(), Err("status 404".to_string()));
+    /// ```
+    pub fn map_err<F, U>(self, f: F) -> Future<T, U>
+        where F: FnOnce(E) -> U + Send + 'static,
+              U: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => Async::Ok(val),
+            Err(err) => Async::Err(f(err))
+        })
+    }
+
+    /// The error side counterpart of `and_then`. A success passes through with the
+    /// new error type, a failure goes to `f`, which can recover, fail differently or
+    /// continue with another future.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<u32, u32> = Future::err(503);
+    /// let f: Future<u32, String> = f.and_then_err(|code| {
+    ///     if code == 404 { Async::Ok(0) } else { Async::Err(format!("server error {}", code)) }
+    /// });
+    ///
+    /// assert_eq!(f.recv(), Err("server error 503".to_string()));
+    /// ```
+    pub fn and_then_err<F, U>(self, f: F) -> Future<T, U>
+        where F: FnOnce(E) -> Async<T, U> + Send + 'static,
+              U: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => Async::Ok(val),
+            Err(err) => f(err)
+        })
+    }
+
+    /// Same as `wait`. From the 2018 edition on `await` is a keyword and this has to
+    /// be called as `r#await`, `wait` or `recv` don't need that.
+    pub fn await(self) -> Async<T, E> {
+        self.wait()
+    }
+
+    /// Block until the future resolves, following any `Continue` along the way. The
+    /// returned value is always `Async::Ok` or `Async::Err`.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<usize> = Future::unit(5);
+    /// assert!(f.wait().is_ok());
+    /// ```
+    pub fn wait(self) -> Async<T, E> {
+        let mut future = self;
+
+        // A loop rather than recursion, so a long chain of `Continue`s doesn't grow
+        // the stack.
+        loop {
+            match future.inner.recv() {
+                Continue(next) => future = next,
+                val => return val
+            }
+        }
+    }
+
+    /// Block until the future resolves and hand the result to `handler` on the
+    /// calling thread.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<usize> = Future::unit(5);
+    ///
+    /// f.await_with_handler(|val| match val {
+    ///     Async::Ok(n) => assert_eq!(n, 5),
+    ///     _ => panic!("unexpected")
+    /// });
+    /// ```
+    pub fn await_with_handler<F>(self, handler: F)
+        where F: FnOnce(Async<T, E>)
+    {
+        handler(self.wait());
+    }
+
+    /// Block until the future resolves, panicking if it failed.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<usize> = Future::unit(5);
+    /// assert_eq!(f.await_or_panic(), 5);
+    /// ```
+    pub fn await_or_panic(self) -> T
+        where E: fmt::Debug
+    {
+        match self.wait() {
+            Async::Ok(val) => val,
+            Async::Err(err) => panic!("Future failed: {:?}", err),
+            Continue(_) => unreachable!()
+        }
+    }
+
+    /// Block until the future is done, discarding its result. If the computation
+    /// panicked, so does this.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// Future::<(), ()>::new(|| {
+    ///     // flush some buffers...
+    ///     Async::Ok(())
+    /// }).run_to_completion();
+    /// ```
+    pub fn run_to_completion(self) {
+        self.wait();
+    }
+
+    /// Block for at most `dur`, falling back to `default` if the future fails or is
+    /// still pending by then.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, &str> = Future::err("unavailable");
+    /// assert_eq!(f.await_timeout_or_default(Duration::from_millis(10), 0), 0);
+    /// ```
+    pub fn await_timeout_or_default(self, dur: Duration, default: T) -> T {
+        match self.await_until(Instant::now() + dur) {
+            Some(Ok(val)) => val,
+            _ => default
+        }
+    }
+
+    pub fn recv(self) -> Result<T, E> {
+        self.wait().into_result()
+    }
+
+    /// Wrap a value into a `Future` that completes right away.
+    ///
+    /// ## Usage
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let _: Future<usize> = Future::unit(5);
+    /// ```
+    pub fn unit(val: T) -> Future<T, E> {
+        Future::<T, E> {
+            inner: FutureInner::Ready(Ok(val)),
+            executor: None,
+            waker: None
+        }
+    }
+
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let _: Future<usize, &str> = Future::err("foobar");
+    /// ```
+    pub fn err(err: E) -> Future<T, E> {
+        Future::<T, E> {
+            inner: FutureInner::Ready(Err(err)),
+            executor: None,
+            waker: None
+        }
+    }
+
+    /// A future that never resolves, for testing timeouts or as a sentinel in a
+    /// `select`. Waiting on it blocks for good rather than failing. Combinators on it
+    /// give never resolving futures of their own without anything waiting on it.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, &str> = Future::never();
+    /// assert_eq!(f.timeout(Duration::from_millis(5), "timed out").recv(), Err("timed out"));
+    /// ```
+    pub fn never() -> Future<T, E> {
+        Future::<T, E> {
+            inner: FutureInner::Never,
+            executor: None,
+            waker: None
+        }
+    }
+
+    /// Recover from an error with another future. `Ok` values pass through untouched,
+    /// on `Err` the future returned by `f` takes over the rest of the chain.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<usize, &str> = Future::err("primary failed");
+    /// let f: Future<usize, ()> = f.flat_map_err(|_| Future::unit(5));
+    ///
+    /// assert_eq!(f.recv(), Ok(5));
+    /// ```
+    pub fn flat_map_err<F2, G>(self, f: G) -> Future<T, F2>
+        where G: FnOnce(E) -> Future<T, F2> + Send + 'static,
+              F2: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => Async::Ok(val),
+            Err(err) => Continue(f(err))
+        })
+    }
+
+    /// Run all futures concurrently and resolve with the first one that succeeds.
+    /// If none do, every error is handed back in the same order as `futures`.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let replicas: Vec<Future<u32, &str>> = vec![
+    ///     Future::err("down"),
+    ///     Future::new(|| Async::Ok(7))
+    /// ];
+    ///
+    /// assert_eq!(Future::reduce_err(replicas).recv(), Ok(7));
+    /// ```
+    pub fn reduce_err(futures: Vec<Future<T, E>>) -> Future<T, Vec<E>> {
+        let len = futures.len();
+
+        if len == 0 {
+            return Future::err(Vec::new());
+        }
+
+        let errors = (0..len).map(|_| None).collect::<Vec<Option<E>>>();
+
+        Future::gather(futures, (errors, 0), move |&mut (ref mut errors, ref mut failed), i, val| {
+            match val {
+                Ok(val) => Some(Async::Ok(val)),
+                Err(err) => {
+                    errors[i] = Some(err);
+                    *failed += 1;
+
+                    if *failed == len {
+                        Some(Async::Err(errors.drain(..).map(|err| err.unwrap()).collect()))
+                    } else {
+                        None
+                    }
+                }
+            }
+        })
+    }
+
+    /// Same as `reduce_err`, under the name for racing replicas against each other.
+    /// Once one succeeds the rest are left to finish and their results are dropped.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let servers: Vec<Future<u32, &str>> = vec![Future::err("refused"), Future::err("timed out")];
+    /// assert_eq!(Future::any(servers).recv(), Err(vec!["refused", "timed out"]));
+    /// ```
+    pub fn any(futures: Vec<Future<T, E>>) -> Future<T, Vec<E>> {
+        Future::reduce_err(futures)
+    }
+
+    /// Apply a `Result`-taking function to whatever this future resolves to.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<&str, ()> = Future::unit("12");
+    /// let f: Future<u32, String> = f.map_result(|res| match res {
+    ///     Ok(s) => s.parse().map_err(|_| "not a number".to_string()),
+    ///     Err(_) => Err("upstream failed".to_string())
+    /// });
+    ///
+    /// assert_eq!(f.recv(), Ok(12));
+    /// ```
+    pub fn map_result<U, F2, G>(self, f: G) -> Future<U, F2>
+        where G: FnOnce(Result<T, E>) -> Result<U, F2> + Send + 'static,
+              U: Send + 'static,
+              F2: Send + 'static
+    {
+        self.transform(move |val| Async::lift_result(f(val)))
+    }
+
+    /// Start `f` for every item at once and wait for all of them, short-circuiting on
+    /// the first error. Unlike collecting the results, the success values are thrown
+    /// away, which suits fan-out side effects like parallel writes.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let done: Future<(), ()> = Future::for_each_parallel(vec![1, 2, 3], |n| {
+    ///     Future::new(move || {
+    ///         // write `n` somewhere...
+    ///         Async::Ok(())
+    ///     })
+    /// });
+    ///
+    /// assert!(done.recv().is_ok());
+    /// ```
+    pub fn for_each_parallel<F>(items: Vec<T>, f: F) -> Future<(), E>
+        where F: Fn(T) -> Future<(), E>
+    {
+        let futures = items.into_iter().map(f).collect::<Vec<_>>();
+        let len = futures.len();
+
+        if len == 0 {
+            return Future::unit(());
+        }
+
+        Future::gather(futures, 0, move |done, _, val| {
+            match val {
+                Ok(()) => {
+                    *done += 1;
+
+                    if *done == len { Some(Async::Ok(())) } else { None }
+                },
+                Err(err) => Some(Async::Err(err))
+            }
+        })
+    }
+
+    /// Turn a failure into `None`. The returned future can't fail itself, which makes
+    /// it handy for collecting optional results without propagating errors.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, &str> = Future::err("missing");
+    /// assert_eq!(f.into_option().recv(), Ok(None));
+    /// ```
+    pub fn into_option(self) -> Future<Option<T>, Never> {
+        self.transform(|val| Async::Ok(val.ok()))
+    }
+
+    /// Validate a successful value with `pred`, replacing it with the future returned
+    /// by `recover` when the check fails. Errors pass straight through.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let cached: Future<u32> = Future::unit(1);
+    /// let fresh = cached.ensure_or_recover(|&version| version >= 2, |_| Future::unit(2));
+    ///
+    /// assert_eq!(fresh.recv(), Ok(2));
+    /// ```
+    pub fn ensure_or_recover<P, R>(self, pred: P, recover: R) -> Future<T, E>
+        where P: FnOnce(&T) -> bool + Send + 'static,
+              R: FnOnce(T) -> Future<T, E> + Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => if pred(&val) { Async::Ok(val) } else { Continue(recover(val)) },
+            Err(err) => Async::Err(err)
+        })
+    }
+
+    /// Wait for every future and report each outcome next to the index of the future
+    /// that produced it, in input order. The combined future never fails.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let futures: Vec<Future<u32, &str>> = vec![Future::unit(1), Future::err("bad")];
+    ///
+    /// assert_eq!(Future::sequence_results_indexed(futures).recv(),
+    ///            Ok(vec![(0, Ok(1)), (1, Err("bad"))]));
+    /// ```
+    pub fn sequence_results_indexed(futures: Vec<Future<T, E>>) -> Future<Vec<(usize, Result<T, E>)>, Never> {
+        let len = futures.len();
+
+        if len == 0 {
+            return Future::unit(Vec::new());
+        }
+
+        let results = (0..len).map(|_| None).collect::<Vec<Option<Result<T, E>>>>();
+
+        Future::gather(futures, (results, 0), move |&mut (ref mut results, ref mut done), i, val| {
+            results[i] = Some(val);
+            *done += 1;
+
+            if *done == len {
+                Some(Async::Ok(results.drain(..).map(|val| val.unwrap()).enumerate().collect()))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Like `and_then`, but for a continuation that itself has to work out, later on,
+    /// which future to run next. Both levels are followed before the chain resolves.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32> = Future::unit(2);
+    /// let f = f.and_then_flatten(|n| Future::unit(Future::unit(n * 10)));
+    ///
+    /// assert_eq!(f.recv(), Ok(20));
+    /// ```
+    pub fn and_then_flatten<S, F>(self, f: F) -> Future<S, E>
+        where F: FnOnce(T) -> Future<Future<S, E>, E> + Send + 'static,
+              S: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => Continue(f(val).transform(|inner| match inner {
+                Ok(inner) => Continue(inner),
+                Err(err) => Async::Err(err)
+            })),
+            Err(err) => Async::Err(err)
+        })
+    }
+
+    /// Report on `tx` when this future is being watched and, with a copy of the
+    /// result, when it completes. The future itself is left as it was, so many
+    /// futures can share one monitoring channel.
+    ///
+    /// ```
+    /// use std::sync::mpsc::channel;
+    /// use tangle::{Future, ProbeEvent};
+    ///
+    /// let (tx, rx) = channel();
+    /// let f: Future<u32> = Future::unit(5).probe(tx);
+    ///
+    /// assert_eq!(f.recv(), Ok(5));
+    ///
+    /// match rx.recv().unwrap() {
+    ///     ProbeEvent::Started => {},
+    ///     _ => panic!("unexpected")
+    /// }
+    /// ```
+    pub fn probe(self, tx: Sender<ProbeEvent<T, E>>) -> Future<T, E>
+        where T: Clone,
+              E: Clone
+    {
+        let _ = tx.send(ProbeEvent::Started);
+
+        self.transform(move |val| {
+            let _ = tx.send(ProbeEvent::Completed(match val {
+                Ok(ref val) => Async::Ok(val.clone()),
+                Err(ref err) => Async::Err(err.clone())
+            }));
+
+            Async::lift_result(val)
+        })
+    }
+
+    /// Rework the deferred part of a chain. If this future hands back a `Continue`,
+    /// `f` gets to wrap the future inside it, while values that are already resolved
+    /// pass through as they are.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<u32> = Future::unit(1).and_then(|n| Async::Continue(Future::unit(n + 1)));
+    /// let f = f.map_continue(|next| next.map(|n| n * 10));
+    ///
+    /// assert_eq!(f.recv(), Ok(20));
+    /// ```
+    pub fn map_continue<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(Future<T, E>) -> Future<T, E> + Send + 'static
+    {
+        self.process(move |val| match val {
+            Continue(next) => Continue(f(next)),
+            val => val
+        })
+    }
+
+    /// Pass any error to `logger` on its way through. The future resolves to the
+    /// same value either way.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, &str> = Future::err("connection refused");
+    /// let f = f.log_on_err(|err| eprintln!("future failed: {:?}", err));
+    ///
+    /// assert_eq!(f.recv(), Err("connection refused"));
+    /// ```
+    pub fn log_on_err<L>(self, logger: L) -> Future<T, E>
+        where L: Fn(&E) + Send + Sync + 'static
+    {
+        self.transform(move |val| {
+            if let Err(ref err) = val {
+                logger(err);
+            }
+
+            Async::lift_result(val)
+        })
+    }
+
+    /// Run `f` over every item in parallel, tolerating up to `max_errors` failures.
+    /// Once more than that have failed the whole thing stops early. Either way the
+    /// combined future succeeds with whatever results and errors had arrived, each
+    /// kept in input order. A `max_errors` of zero stops at the first failure.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let imported = Future::map_while_ok(vec![1, 2, 3], |n| {
+    ///     if n == 2 { Future::err("bad row") } else { Future::unit(n * 10) }
+    /// }, 1);
+    ///
+    /// assert_eq!(imported.recv(), Ok((vec![10, 30], vec!["bad row"])));
+    /// ```
+    pub fn map_while_ok<U, F>(items: Vec<T>, f: F, max_errors: usize) -> Future<(Vec<U>, Vec<E>), Never>
+        where F: FnMut(T) -> Future<U, E>,
+              U: Send + 'static
+    {
+        let futures = items.into_iter().map(f).collect::<Vec<_>>();
+        let len = futures.len();
+
+        if len == 0 {
+            return Future::unit((Vec::new(), Vec::new()));
+        }
+
+        let results = (0..len).map(|_| None).collect::<Vec<Option<Result<U, E>>>>();
+
+        Future::gather(futures, (results, 0, 0), move |&mut (ref mut results, ref mut done, ref mut failed), i, val| {
+            if val.is_err() {
+                *failed += 1;
+            }
+
+            results[i] = Some(val);
+            *done += 1;
+
+            if *done < len && *failed <= max_errors {
+                return None;
+            }
+
+            let mut values = Vec::new();
+            let mut errors = Vec::new();
+
+            for val in results.drain(..) {
+                match val {
+                    Some(Ok(val)) => values.push(val),
+                    Some(Err(err)) => errors.push(err),
+                    None => {}
+                }
+            }
+
+            Some(Async::Ok((values, errors)))
+        })
+    }
+
+    /// Same as `into_option`, the error is dropped without a trace.
+    pub fn as_option(self) -> Future<Option<T>, Never> {
+        self.into_option()
+    }
+
+    /// Like `as_option`, but the error is handed to `logger` before it's dropped so
+    /// failures don't go unnoticed.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, &str> = Future::err("timed out");
+    /// let f = f.as_option_or_log(|err| eprintln!("dropping error: {}", err));
+    ///
+    /// assert_eq!(f.recv(), Ok(None));
+    /// ```
+    pub fn as_option_or_log<L>(self, logger: L) -> Future<Option<T>, Never>
+        where L: Fn(&E) + Send + Sync + 'static
+    {
+        self.log_on_err(logger).into_option()
+    }
+
+    /// Resolve as soon as `n` of the futures have succeeded, with their values in the
+    /// order they arrived. When too many fail for that to happen, every future is
+    /// still waited for and the last error to arrive is returned. Panics if `n` is
+    /// larger than `futures`.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let replicas: Vec<Future<u32, &str>> = vec![
+    ///     Future::unit(1),
+    ///     Future::err("down"),
+    ///     Future::unit(1)
+    /// ];
+    ///
+    /// assert_eq!(Future::await_n(replicas, 2).recv(), Ok(vec![1, 1]));
+    /// ```
+    pub fn await_n(futures: Vec<Future<T, E>>, n: usize) -> Future<Vec<T>, E> {
+        let len = futures.len();
+
+        assert!(n <= len, "cannot wait for {} successes out of {} futures.", n, len);
+
+        if n == 0 {
+            return Future::unit(Vec::new());
+        }
+
+        Future::gather(futures, (Vec::with_capacity(n), 0), move |&mut (ref mut values, ref mut failed), _, val| {
+            match val {
+                Ok(val) => {
+                    values.push(val);
+
+                    if values.len() == n { Some(Async::Ok(mem::take(values))) } else { None }
+                },
+                Err(err) => {
+                    *failed += 1;
+
+                    if *failed > len - n && *failed + values.len() == len {
+                        Some(Async::Err(err))
+                    } else {
+                        None
+                    }
+                }
+            }
+        })
+    }
+
+    /// Resolve every function and apply each one to its own copy of `input`, keeping
+    /// the results in the same order as `functions`. The first error wins.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let double: Future<fn(u32) -> u32> = Future::unit(|n| n * 2);
+    /// let square: Future<fn(u32) -> u32> = Future::unit(|n| n * n);
+    ///
+    /// assert_eq!(Future::apply_all(vec![double, square], 3).recv(), Ok(vec![6, 9]));
+    /// ```
+    pub fn apply_all<F, U>(functions: Vec<Future<F, E>>, input: T) -> Future<Vec<U>, E>
+        where F: FnOnce(T) -> U + Send + 'static,
+              T: Clone,
+              U: Send + 'static
+    {
+        Future::join_all(functions).map(move |functions| {
+            functions.into_iter().map(|f| f(input.clone())).collect()
+        })
+    }
+
+    /// Turn this into a `SharedFuture` so several consumers can wait on the result.
+    /// If the sender goes away without resolving this future, waiting on the shared
+    /// one panics instead of blocking for good.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32> = Future::unit(5);
+    /// let shared = f.shared();
+    /// let other = shared.clone();
+    ///
+    /// assert_eq!(shared.wait().unwrap(), 5);
+    /// assert_eq!(other.wait().unwrap(), 5);
+    /// ```
+    pub fn shared(self) -> SharedFuture<T, E>
+        where T: Clone,
+              E: Clone
+    {
+        let inner = Arc::new((Mutex::new(SharedState::Pending(Vec::new())), Condvar::new()));
+
+        // Its value is never coming, but nothing went away either.
+        if self.is_never() {
+            return SharedFuture { inner };
+        }
+
+        // Dropped along with the closure, which marks the source gone unless it ran.
+        let slot = GoneOnDrop(inner.clone());
+
+        self.on_complete(move |val| slot.settle(SharedState::Done(val)));
+
+        SharedFuture { inner }
+    }
+
+    /// Throw away the success value, for when only the outcome matters.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let written: Future<usize> = Future::unit(512);
+    /// assert_eq!(written.as_unit().recv(), Ok(()));
+    /// ```
+    pub fn as_unit(self) -> Future<(), E> {
+        self.map(|_| ())
+    }
+
+    /// Move the outcome into the success channel so later steps can treat errors as
+    /// ordinary values. The returned future never fails, `dematerialize` undoes this.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<u32, &str> = Future::err("failed");
+    ///
+    /// match f.materialize().recv() {
+    ///     Ok(Async::Err(err)) => assert_eq!(err, "failed"),
+    ///     _ => panic!("unexpected")
+    /// }
+    /// ```
+    pub fn materialize(self) -> Future<Async<T, E>, Never> {
+        self.transform(|val| Async::Ok(Async::lift_result(val)))
+    }
+
+    /// Poll `f` every `poll_interval` until it returns a result, for state that can't
+    /// push a notification, like a file showing up. Gives up with `timeout_err` once
+    /// `max_duration` has passed. Polling happens on its own thread so the sleeps in
+    /// between don't hold up the pool.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<u32, &str> = Future::measure_until(
+    ///     Duration::from_millis(1),
+    ///     Duration::from_secs(1),
+    ///     "timed out",
+    ///     || Some(Async::Ok(5))
+    /// );
+    ///
+    /// assert_eq!(f.recv(), Ok(5));
+    /// ```
+    pub fn measure_until<F>(poll_interval: Duration, max_duration: Duration, timeout_err: E, f: F) -> Future<T, E>
+        where F: Fn() -> Option<Async<T, E>> + Send + 'static
+    {
+        let (tx, rx) = oneshot::channel();
+
+        thread::spawn(move || {
+            let started = Instant::now();
+
+            loop {
+                if let Some(val) = f() {
+                    let _ = tx.send(val);
+                    return;
+                }
+
+                if started.elapsed() >= max_duration {
+                    let _ = tx.send(Async::Err(timeout_err));
+                    return;
+                }
+
+                thread::sleep(poll_interval);
+            }
+        });
+
+        Future::from_oneshot(rx)
+    }
+
+    /// Continue with `f` on success or with `otherwise` on failure.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<u32, &str> = Future::err("cache miss");
+    /// let f = f.chain_if_ok(|n| Async::Ok(n + 1), |_| Async::Ok(0));
+    ///
+    /// assert_eq!(f.recv(), Ok(0));
+    /// ```
+    pub fn chain_if_ok<F, G>(self, f: F, otherwise: G) -> Future<T, E>
+        where F: FnOnce(T) -> Async<T, E> + Send + 'static,
+              G: FnOnce(E) -> Async<T, E> + Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => f(val),
+            Err(err) => otherwise(err)
+        })
+    }
+
+    /// Continue with a single `Result`-taking closure covering both outcomes.
+    pub fn branch_result<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(Result<T, E>) -> Async<T, E> + Send + 'static
+    {
+        self.transform(f)
+    }
+
+    /// Split the success value into two futures that can be composed on their own.
+    /// An error is cloned into both of them.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<(u32, String)> = Future::unit((1, "one".to_string()));
+    /// let (num, name) = f.unzip_with(|pair| pair);
+    ///
+    /// assert_eq!(num.recv(), Ok(1));
+    /// assert_eq!(name.recv(), Ok("one".to_string()));
+    /// ```
+    pub fn unzip_with<A, B, F>(self, f: F) -> (Future<A, E>, Future<B, E>)
+        where F: FnOnce(T) -> (A, B) + Send + 'static,
+              A: Send + 'static,
+              B: Send + 'static,
+              E: Clone
+    {
+        let executor = self.executor.clone();
+        let (tx_a, rx_a) = oneshot::channel();
+        let (tx_b, rx_b) = oneshot::channel();
+
+        self.on_complete(move |val| {
+            match val {
+                Ok(val) => {
+                    let (a, b) = f(val);
+                    let _ = tx_a.send(Async::Ok(a));
+                    let _ = tx_b.send(Async::Ok(b));
+                },
+                Err(err) => {
+                    let _ = tx_a.send(Async::Err(err.clone()));
+                    let _ = tx_b.send(Async::Err(err));
+                }
+            }
+        });
+
+        (Future::from_async_channel_on(executor.clone(), rx_a), Future::from_async_channel_on(executor, rx_b))
+    }
+
+    /// Feed every item into `f` one after another, each step getting the accumulator
+    /// of the one before it. Every step runs on the pool and the first error stops
+    /// the fold.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f = Future::<u32, &str>::fold(vec![1, 2, 3], 0, |sum, n| Async::Ok(sum + n));
+    /// assert_eq!(f.recv(), Ok(6));
+    /// ```
+    pub fn fold<I, F, Acc>(iter: I, init: Acc, f: F) -> Future<Acc, E>
+        where I: IntoIterator<Item = T>,
+              I::IntoIter: Send + 'static,
+              F: Fn(Acc, T) -> Async<Acc, E> + Send + Sync + 'static,
+              Acc: Send + 'static
+    {
+        let (tx, rx) = oneshot::channel();
+
+        Future::fold_step(iter.into_iter(), init, Arc::new(f), tx);
+
+        Future::from_oneshot(rx)
+    }
+
+    /// Wait on all `futures` concurrently, then fold their values with `f` in input
+    /// order, so the result doesn't depend on which future finished first. Fails with
+    /// the first error to arrive.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let parts: Vec<Future<&str>> = vec![Future::unit("a"), Future::unit("b"), Future::unit("c")];
+    /// let joined = Future::aggregate(parts, String::new(), |acc, s| acc + s);
+    ///
+    /// assert_eq!(joined.recv(), Ok("abc".to_string()));
+    /// ```
+    pub fn aggregate<U, F>(futures: Vec<Future<T, E>>, init: U, f: F) -> Future<U, E>
+        where F: FnMut(U, T) -> U + Send + 'static,
+              U: Send + 'static
+    {
+        Future::join_all(futures).map(move |values| values.into_iter().fold(init, f))
+    }
+
+    /// Like `join_all`, except up to `n_failures` errors are tolerated. A failed
+    /// future leaves a `None` in its slot, the rest keep their input order. The error
+    /// that goes over the limit fails the whole thing.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let replicas: Vec<Future<u32, &str>> = vec![Future::unit(1), Future::err("down"), Future::unit(3)];
+    ///
+    /// assert_eq!(Future::fail_after(1, replicas).recv(), Ok(vec![Some(1), None, Some(3)]));
+    /// ```
+    pub fn fail_after(n_failures: usize, futures: Vec<Future<T, E>>) -> Future<Vec<Option<T>>, E> {
+        let len = futures.len();
+
+        if len == 0 {
+            return Future::unit(Vec::new());
+        }
+
+        let values = (0..len).map(|_| None).collect::<Vec<Option<T>>>();
+
+        Future::gather(futures, (values, 0, 0), move |&mut (ref mut values, ref mut done, ref mut failed), i, val| {
+            *done += 1;
+
+            match val {
+                Ok(val) => values[i] = Some(val),
+                Err(err) => {
+                    *failed += 1;
+
+                    if *failed > n_failures {
+                        return Some(Async::Err(err));
+                    }
+                }
+            }
+
+            if *done == len { Some(Async::Ok(mem::take(values))) } else { None }
+        })
+    }
+
+    /// `map` for when the success type stays the same, to make a small touch-up on
+    /// the value stand out from a real conversion.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<String> = Future::unit("hello".to_string());
+    /// assert_eq!(f.then_ok(|s| s.to_uppercase()).recv(), Ok("HELLO".to_string()));
+    /// ```
+    pub fn then_ok<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(T) -> T + Send + 'static
+    {
+        self.map(f)
+    }
+
+    /// Same as `then_ok`, only for the error.
+    pub fn then_err<G>(self, g: G) -> Future<T, E>
+        where G: FnOnce(E) -> E + Send + 'static
+    {
+        self.map_err(g)
+    }
+
+    /// Call `f` with the value on its way through, on the pool, for logging or
+    /// metrics. Failures skip `f` and the value itself passes on unchanged.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32> = Future::unit(5);
+    /// let f = f.on_success(|n| println!("got {}", n));
+    ///
+    /// assert_eq!(f.recv(), Ok(5));
+    /// ```
+    pub fn on_success<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(&T) + Send + 'static
+    {
+        self.transform(move |val| {
+            if let Ok(ref val) = val {
+                f(val);
+            }
+
+            Async::lift_result(val)
+        })
+    }
+
+    /// Same as `on_success`, only for the error.
+    pub fn on_failure<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(&E) + Send + 'static
+    {
+        self.transform(move |val| {
+            if let Err(ref err) = val {
+                f(err);
+            }
+
+            Async::lift_result(val)
+        })
+    }
+
+    /// Look at the outcome on its way through, like `Iterator::inspect`. `f` sees
+    /// either `Ok` or `Err`, never a `Continue`, and the value passes on unchanged.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, &str> = Future::err("timed out");
+    /// let f = f.inspect(|val| println!("resolved with {:?}", val));
+    ///
+    /// assert_eq!(f.recv(), Err("timed out"));
+    /// ```
+    pub fn inspect<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(&Async<T, E>) + Send + 'static
+    {
+        self.transform(move |val| {
+            let val = Async::lift_result(val);
+            f(&val);
+            val
+        })
+    }
+
+    /// Create the futures with `tasks` and have at most `max` of them running at any
+    /// time, collecting their values in input order or failing with the first error.
+    /// A `Future` is already running by the time it exists, so this can't take the
+    /// futures themselves. It takes the closures creating them instead, and only
+    /// calls the next one, in input order, once an earlier future has resolved.
+    /// Keeps a huge batch from crowding everything else out of the pool. Each closure
+    /// picks where its future runs, with `Future::new_on` say. A future whose sender
+    /// goes away without sending anything, a panicking task say, disconnects the
+    /// combined future the same way.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let tasks = (0..10).map(|n| move || Future::new(move || Async::Ok(n * 2))).collect::<Vec<_>>();
+    /// let f: Future<Vec<u32>> = Future::limit_parallelism(tasks, 2);
+    ///
+    /// assert_eq!(f.recv().unwrap()[9], 18);
+    /// ```
+    pub fn limit_parallelism<F>(tasks: Vec<F>, max: usize) -> Future<Vec<T>, E>
+        where F: FnOnce() -> Future<T, E> + Send + 'static
+    {
+        assert!(max > 0, "cannot run futures with a parallelism of 0.");
+
+        if tasks.is_empty() {
+            return Future::unit(Vec::new());
+        }
+
+        let (tx, rx) = oneshot::channel();
+
+        let limit = Arc::new(Limit {
+            remaining: AtomicUsize::new(tasks.len()),
+            values: Mutex::new((0..tasks.len()).map(|_| None).collect()),
+            tasks: Mutex::new(tasks.into_iter().enumerate()),
+            tx: Mutex::new(Some(tx))
+        });
+
+        for _ in 0..max {
+            Limit::submit(&limit);
+        }
+
+        Future::from_oneshot(rx)
+    }
+
+    /// Call `reporter` every `interval` until this future resolves, for heartbeats or
+    /// progress output on long running work. The result passes through untouched.
+    /// Reporting also stops if the sender goes away without sending anything.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32> = Future::unit(5);
+    /// let f = f.progress(|| println!("still working..."), Duration::from_millis(100));
+    ///
+    /// assert_eq!(f.recv(), Ok(5));
+    /// ```
+    pub fn progress<F>(self, reporter: F, interval: Duration) -> Future<T, E>
+        where F: Fn() + Send + Sync + 'static
+    {
+        let executor = self.executor.clone();
+        let (tx, rx) = oneshot::channel();
+        let done = Arc::new(AtomicBool::new(false));
+        let finished = done.clone();
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+
+                if done.load(Ordering::SeqCst) {
+                    return;
+                }
+
+                reporter();
+            }
+        });
+
+        // Dropped along with the closure, so it's set even if the closure never runs.
+        let finished = SetOnDrop(finished);
+
+        self.on_complete(move |val| {
+            let _finished = finished;
+            let _ = tx.send(Async::lift_result(val));
+        });
+
+        Future::from_async_channel_on(executor, rx)
+    }
+
+    /// Call `between` every time the chain hands over to the future in a `Continue`,
+    /// before that next link is waited on. Good for trace points or checkpoints in
+    /// long chains. Nothing is called for a future that resolves directly.
+    ///
+    /// ```
+    /// use tangle::{Future, Async, Continue};
+    ///
+    /// let f: Future<u32> = Future::new(|| Continue(Future::new(|| Async::Ok(1))));
+    /// let f = f.interpose(|| println!("next link"));
+    ///
+    /// assert_eq!(f.recv(), Ok(1));
+    /// ```
+    pub fn interpose<G>(self, between: G) -> Future<T, E>
+        where G: Fn() + Send + Sync + 'static
+    {
+        fn step<T, E, G>(future: Future<T, E>, between: Arc<G>) -> Future<T, E>
+            where T: Send + 'static,
+                  E: Send + 'static,
+                  G: Fn() + Send + Sync + 'static
+        {
+            future.process(move |val| match val {
+                Continue(next) => {
+                    between();
+                    Continue(step(next, between))
+                },
+                val => val
+            })
+        }
+
+        step(self, Arc::new(between))
+    }
+
+    /// Tie the result to a `CancelOnDrop` guard. Once the guard is dropped the future
+    /// fails with `Cancelled` right away, unless it resolved first, so the work lives
+    /// only as long as whoever owns the guard. The computation itself is already
+    /// running and isn't interrupted, its result is dropped.
+    ///
+    /// ```
+    /// use tangle::{Promise, Cancelled};
+    ///
+    /// let mut p = Promise::<u32, Cancelled>::new();
+    /// let (f, guard) = p.future().with_cancel_on_drop();
+    ///
+    /// drop(guard);
+    /// p.resolve(5);
+    ///
+    /// assert_eq!(f.recv(), Err(Cancelled));
+    /// ```
+    pub fn with_cancel_on_drop(self) -> (Future<T, E>, CancelOnDrop)
+        where E: From<Cancelled>
+    {
+        let (token, handle) = CancellationToken::new();
+
+        (self.with_cancellation(handle), CancelOnDrop { token })
+    }
+
+    /// Start a future for every item with `f` and collect their values in input
+    /// order, or fail with the first error. Every future is created before any of
+    /// them is waited on, so they all run concurrently.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f = Future::<u32>::traverse(vec![1, 2, 3], |n| Future::new(move || Async::Ok(n * 2)));
+    /// assert_eq!(f.recv(), Ok(vec![2, 4, 6]));
+    /// ```
+    pub fn traverse<I, F, U>(iter: I, f: F) -> Future<Vec<U>, E>
+        where I: IntoIterator<Item = T>,
+              F: Fn(T) -> Future<U, E> + Send + Sync + 'static,
+              U: Send + 'static
+    {
+        Future::join_all(iter.into_iter().map(f).collect())
+    }
+
+    /// Expand every item into any number of futures with `f` and run all of them at
+    /// once, flattening their values in input order. Fails with the first error.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f = Future::<u32>::par_flat_map(vec![1, 2], |n| {
+    ///     (0..n).map(|i| Future::<u32>::unit(n * 10 + i)).collect()
+    /// });
+    ///
+    /// assert_eq!(f.recv(), Ok(vec![10, 20, 21]));
+    /// ```
+    pub fn par_flat_map<U, F>(items: Vec<T>, f: F) -> Future<Vec<U>, E>
+        where F: Fn(T) -> Vec<Future<U, E>> + Send + Sync + 'static,
+              U: Send + 'static
+    {
+        let futures = items.into_iter().flat_map(f).collect();
+
+        Future::join_all(futures)
+    }
+
+    /// Same as `par_flat_map`, except only `concurrency` items are expanded at a
+    /// time. The next item's futures are created once all futures of an earlier item
+    /// have resolved.
+    pub fn par_flat_map_bounded<U, F>(items: Vec<T>, concurrency: usize, f: F) -> Future<Vec<U>, E>
+        where F: Fn(T) -> Vec<Future<U, E>> + Send + Sync + 'static,
+              U: Send + 'static
+    {
+        let f = Arc::new(f);
+
+        let tasks = items.into_iter().map(|item| {
+            let f = f.clone();
+
+            move || Future::join_all(f(item))
+        }).collect();
+
+        Future::limit_parallelism(tasks, concurrency).map(|groups: Vec<Vec<U>>| {
+            groups.into_iter().flatten().collect()
+        })
+    }
+
+    /// Pair the values of two futures that run side by side. If either fails the
+    /// pair does too, and when both fail the error of `self` is the one kept.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let user: Future<&str> = Future::new(|| Async::Ok("ferris"));
+    /// let posts: Future<u32> = Future::new(|| Async::Ok(3));
+    ///
+    /// assert_eq!(user.zip(posts).recv(), Ok(("ferris", 3)));
+    /// ```
+    pub fn zip<U>(self, other: Future<U, E>) -> Future<(T, U), E>
+        where U: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(t) => Continue(other.map(move |u| (t, u))),
+            Err(err) => Async::Err(err)
+        })
+    }
+
+    /// Combine the values of both futures with `f` once both have succeeded. Both
+    /// run concurrently, and `f` is skipped if either fails.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let price: Future<u32> = Future::unit(20);
+    /// let quantity: Future<u32> = Future::unit(3);
+    ///
+    /// assert_eq!(price.zip_with(quantity, |p, q| p * q).recv(), Ok(60));
+    /// ```
+    pub fn zip_with<U, S, F>(self, other: Future<U, E>, f: F) -> Future<S, E>
+        where U: Send + 'static,
+              S: Send + 'static,
+              F: FnOnce(T, U) -> S + Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(t) => Continue(other.map(move |u| f(t, u))),
+            Err(err) => Async::Err(err)
+        })
+    }
+
+    /// Resolve with whichever of the two futures resolves first, success or not. The
+    /// other one is left to finish on its own and its result is dropped.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::{Future, Async, Either};
+    ///
+    /// let slow: Future<u32> = Future::delayed(Duration::from_millis(50), || Async::Ok(1));
+    /// let fast: Future<&str> = Future::unit("cached");
+    ///
+    /// assert_eq!(slow.select(fast).recv(), Ok(Either::Right("cached")));
+    /// ```
+    pub fn select<U>(self, other: Future<U, E>) -> Future<Either<T, U>, E>
+        where U: Send + 'static
+    {
+        let executor = self.executor.clone();
+        let (tx, rx) = oneshot::channel();
+        let winner = Arc::new(Mutex::new(Some(tx)));
+        let other_winner = winner.clone();
+
+        self.on_complete(move |val| {
+            if let Some(tx) = winner.lock().expect("error acquiring a lock.").take() {
+                let _ = tx.send(Async::lift_result(val.map(Either::Left)));
+            }
+        });
+
+        other.on_complete(move |val| {
+            if let Some(tx) = other_winner.lock().expect("error acquiring a lock.").take() {
+                let _ = tx.send(Async::lift_result(val.map(Either::Right)));
+            }
+        });
+
+        Future::from_async_channel_on(executor, rx)
+    }
+
+    /// Wait for all futures, keeping their values in input order, or fail with the
+    /// first error to arrive. All of them are already running, so nothing is
+    /// serialized, and the waiting happens off the pool.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let futures = (0..3).map(|n| Future::<u32>::new(move || Async::Ok(n))).collect();
+    /// assert_eq!(Future::join_all(futures).recv(), Ok(vec![0, 1, 2]));
+    /// ```
+    pub fn join_all(futures: Vec<Future<T, E>>) -> Future<Vec<T>, E> {
+        let len = futures.len();
+
+        if len == 0 {
+            return Future::unit(Vec::new());
+        }
+
+        let values = (0..len).map(|_| None).collect::<Vec<Option<T>>>();
+
+        Future::gather(futures, (values, 0), move |&mut (ref mut values, ref mut done), i, val| {
+            match val {
+                Ok(val) => {
+                    values[i] = Some(val);
+                    *done += 1;
+
+                    if *done == len {
+                        Some(Async::Ok(values.drain(..).map(|val| val.unwrap()).collect()))
+                    } else {
+                        None
+                    }
+                },
+                Err(err) => Some(Async::Err(err))
+            }
+        })
+    }
+
+    /// Create `n` futures with `f`, all of them running right away. Handy to feed
+    /// into `join_all` or `await_n`.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let replicas = Future::n_times(3, || Future::<u32>::new(|| Async::Ok(7)));
+    /// assert_eq!(Future::join_all(replicas).recv(), Ok(vec![7, 7, 7]));
+    /// ```
+    pub fn n_times<F>(n: usize, f: F) -> Vec<Future<T, E>>
+        where F: Fn() -> Future<T, E> + Send + Sync + 'static
+    {
+        (0..n).map(|_| f()).collect()
+    }
+
+    /// Fail with `RecursionLimitExceeded` once more than `n` `Continue`s have been
+    /// followed, instead of letting a runaway chain grow until the stack overflows.
+    ///
+    /// ```
+    /// use tangle::{Future, Async, Continue, RecursionLimitExceeded};
+    ///
+    /// fn countdown(n: u32) -> Future<u32, RecursionLimitExceeded> {
+    ///     Future::new(move || if n == 0 { Async::Ok(0) } else { Continue(countdown(n - 1)) })
+    /// }
+    ///
+    /// assert_eq!(countdown(3).with_recursion_limit(5).recv(), Ok(0));
+    /// assert_eq!(countdown(10).with_recursion_limit(5).recv(), Err(RecursionLimitExceeded));
+    /// ```
+    pub fn with_recursion_limit(self, n: usize) -> Future<T, E>
+        where E: From<RecursionLimitExceeded>
+    {
+        fn step<T, E>(future: Future<T, E>, depth: usize, limit: usize) -> Future<T, E>
+            where T: Send + 'static,
+                  E: Send + From<RecursionLimitExceeded> + 'static
+        {
+            future.process(move |val| match val {
+                Continue(_) if depth == limit => Async::Err(E::from(RecursionLimitExceeded)),
+                Continue(next) => Continue(step(next, depth + 1, limit)),
+                val => val
+            })
+        }
+
+        step(self, 0, n)
+    }
+
+    /// `with_recursion_limit` using the global limit, see `set_max_continuation_depth`.
+    pub fn with_default_recursion_limit(self) -> Future<T, E>
+        where E: From<RecursionLimitExceeded>
+    {
+        self.with_recursion_limit(max_continuation_depth())
+    }
+
+    /// Fail with `err` if the future hasn't resolved within `dur`, otherwise pass its
+    /// result through. The timer is a sleeping thread of its own. When it fires first
+    /// the original computation still runs to completion, its result is dropped.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::Promise;
+    ///
+    /// let mut p = Promise::<u32, &str>::new();
+    /// let f = p.future().timeout(Duration::from_millis(10), "timed out");
+    ///
+    /// assert_eq!(f.recv(), Err("timed out"));
+    /// ```
+    pub fn timeout(self, dur: Duration, err: E) -> Future<T, E> {
+        self.timeout_on(thread::Builder::new(), dur, err)
+    }
+
+    /// Turn an error into a success value, like `Result::unwrap_or_else`.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32, &str> = Future::err("not found");
+    /// assert_eq!(f.recover(|_| 0).recv(), Ok(0));
+    /// ```
+    pub fn recover<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(E) -> T + Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => Async::Ok(val),
+            Err(err) => Async::Ok(f(err))
+        })
+    }
+
+    /// Replace a failed future with the one `f` returns, for fallback chains.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let primary: Future<&str, &str> = Future::err("primary down");
+    /// let f = primary.recover_with(|_| Future::unit("replica"));
+    ///
+    /// assert_eq!(f.recv(), Ok("replica"));
+    /// ```
+    pub fn recover_with<F>(self, f: F) -> Future<T, E>
+        where F: FnOnce(E) -> Future<T, E> + Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(val) => Async::Ok(val),
+            Err(err) => Continue(f(err))
+        })
+    }
+
+    /// Convert the error with `From`, the way `?` would.
+    ///
+    /// ```
+    /// use std::num::ParseIntError;
+    /// use tangle::Future;
+    ///
+    /// #[derive(Debug)]
+    /// enum AppError { Parse(ParseIntError) }
+    ///
+    /// impl From<ParseIntError> for AppError {
+    ///     fn from(err: ParseIntError) -> AppError { AppError::Parse(err) }
+    /// }
+    ///
+    /// let f: Future<u32, ParseIntError> = Future::err("x".parse::<u32>().unwrap_err());
+    /// let f: Future<u32, AppError> = f.coerce_err();
+    ///
+    /// assert!(f.recv().is_err());
+    /// ```
+    pub fn coerce_err<F2>(self) -> Future<T, F2>
+        where F2: From<E> + Send + 'static
+    {
+        self.map_err(F2::from)
+    }
+
+    /// Wait for `self` and `other`, which run side by side, and continue with both
+    /// values. Same as `zip` followed by `and_then`, without the tuple in between.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let price: Future<u32> = Future::unit(20);
+    /// let quantity: Future<u32> = Future::unit(3);
+    ///
+    /// assert_eq!(price.zip_and_then(quantity, |p, q| Async::Ok(p * q)).recv(), Ok(60));
+    /// ```
+    pub fn zip_and_then<B, S, F>(self, other: Future<B, E>, f: F) -> Future<S, E>
+        where F: FnOnce(T, B) -> Async<S, E> + Send + 'static,
+              B: Send + 'static,
+              S: Send + 'static
+    {
+        self.transform(move |val| match val {
+            Ok(a) => Continue(other.and_then(move |b| f(a, b))),
+            Err(err) => Async::Err(err)
+        })
+    }
+
+    /// Also send a copy of the success value on `tx`, for a consumer outside of the
+    /// chain like a UI thread. Errors aren't forwarded, the future itself resolves as
+    /// it normally would.
+    ///
+    /// ```
+    /// use std::sync::mpsc::channel;
+    /// use tangle::Future;
+    ///
+    /// let (tx, rx) = channel();
+    /// let f: Future<u32> = Future::unit(5);
+    ///
+    /// assert_eq!(f.on_resolve(tx).recv(), Ok(5));
+    /// assert_eq!(rx.recv().unwrap(), 5);
+    /// ```
+    pub fn on_resolve(self, tx: Sender<T>) -> Future<T, E>
+        where T: Clone
+    {
+        self.map(move |val| {
+            let _ = tx.send(val.clone());
+            val
+        })
+    }
+
+    /// Same as `on_resolve`, except the copy goes to `promise`. A promise that was
+    /// already settled elsewhere keeps its value, the copy is then dropped.
+    pub fn on_resolve_async<F2>(self, mut promise: Promise<T, F2>) -> Future<T, E>
+        where T: Clone,
+              F2: Send + 'static
+    {
+        self.map(move |val| {
+            let _ = promise.try_resolve(val.clone());
+            val
+        })
+    }
+
+    /// Continue with either outcome. `f` gets the resolved value, `Ok` or `Err` but
+    /// never `Continue`, which makes it the place for finally-style cleanup.
+    ///
+    /// ```
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<u32, &str> = Future::err("failed");
+    /// let f: Future<String, ()> = f.then(|val| match val {
+    ///     Async::Ok(n) => Async::Ok(n.to_string()),
+    ///     _ => Async::Ok("none".to_string())
+    /// });
+    ///
+    /// assert_eq!(f.recv(), Ok("none".to_string()));
+    /// ```
+    pub fn then<F, S, U>(self, f: F) -> Future<S, U>
+        where F: FnOnce(Async<T, E>) -> Async<S, U> + Send + 'static,
+              S: Send + 'static,
+              U: Send + 'static
+    {
+        self.transform(move |val| f(Async::lift_result(val)))
+    }
+
+    /// Map the value with `f`, failing with `err` when it returns `None`.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<&str, &str> = Future::unit("42");
+    /// let f = f.filter_map(|s| s.parse::<u32>().ok(), "not a number");
+    ///
+    /// assert_eq!(f.recv(), Ok(42));
+    /// ```
+    pub fn filter_map<F, S>(self, f: F, err: E) -> Future<S, E>
+        where F: FnOnce(T) -> Option<S> + Send + 'static,
+              S: Send + 'static
+    {
+        self.filter_map_or_else(f, move || err)
+    }
+
+    /// Same as `filter_map`, with the error only built by `err` when it's needed.
+    pub fn filter_map_or_else<F, G, S>(self, f: F, err: G) -> Future<S, E>
+        where F: FnOnce(T) -> Option<S> + Send + 'static,
+              G: FnOnce() -> E + Send + 'static,
+              S: Send + 'static
+    {
+        self.and_then(move |val| Async::lift_option(f(val), err))
+    }
+
+    /// Make up to `attempts` futures with `factory`, starting the next one whenever
+    /// the previous one fails. Resolves with the first success, or with the last error
+    /// once every attempt has failed.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use tangle::{Future, Async};
+    ///
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let counter = calls.clone();
+    ///
+    /// let f = Future::retry(move || {
+    ///     let n = counter.fetch_add(1, Ordering::SeqCst);
+    ///     Future::new(move || if n < 2 { Async::Err("flaky") } else { Async::Ok(n) })
+    /// }, 5);
+    ///
+    /// assert_eq!(f.recv(), Ok(2));
+    /// ```
+    pub fn retry<F>(factory: F, attempts: usize) -> Future<T, E>
+        where F: Fn() -> Future<T, E> + Send + 'static
+    {
+        Future::retry_after(factory, attempts, None)
+    }
+
+    /// Same as `retry`, waiting `backoff` before each new attempt. The wait happens
+    /// on a thread of its own.
+    pub fn retry_with_backoff<F>(factory: F, attempts: usize, backoff: Duration) -> Future<T, E>
+        where F: Fn() -> Future<T, E> + Send + 'static
+    {
+        Future::retry_after(factory, attempts, Some(backoff))
+    }
+
+    /// Take the value if it's already there, without blocking. A future that hasn't
+    /// resolved yet is handed back untouched so it can be tried again, or awaited.
+    /// Like `wait`, this panics if the promise or the computation behind the future
+    /// went away without settling it.
+    ///
+    /// ```
+    /// use tangle::{Future, Promise};
+    ///
+    /// let mut promise = Promise::<u32, ()>::new();
+    /// let f = promise.future();
+    ///
+    /// let f = f.try_await().unwrap_err();
+    /// promise.resolve(5);
+    ///
+    /// assert_eq!(f.try_await().unwrap().unwrap(), 5);
+    /// ```
+    pub fn try_await(mut self) -> Result<Async<T, E>, Future<T, E>> {
+        loop {
+            let val = match self.inner {
+                FutureInner::Ready(val) => return Ok(Async::lift_result(val)),
+                FutureInner::Never => return Err(self),
+                FutureInner::Channel(ref rx) => match rx.try_recv() {
+                    Ok(val) => val,
+                    Err(TryRecvError::Empty) => return Err(self),
+                    Err(TryRecvError::Disconnected) => panic!("error trying to wait for channel.")
+                }
+            };
+
+            match val {
+                Continue(next) => {
+                    self.inner = next.inner;
+                    self.waker = None;
+                },
+                val => return Ok(val)
+            }
+        }
+    }
+
+    /// Fail with `Cancelled` as soon as the token paired with `handle` is cancelled,
+    /// unless this future resolved first. Cancelling doesn't stop the work already
+    /// running, its result is dropped.
+    ///
+    /// ```
+    /// use tangle::{CancellationToken, Cancelled, Promise};
+    ///
+    /// let (token, handle) = CancellationToken::new();
+    /// let mut p = Promise::<u32, Cancelled>::new();
+    /// let f = p.future().with_cancellation(handle);
+    ///
+    /// token.cancel();
+    /// assert_eq!(f.recv(), Err(Cancelled));
+    /// ```
+    pub fn with_cancellation(self, handle: CancellationHandle) -> Future<T, E>
+        where E: From<Cancelled>
+    {
+        let executor = self.executor.clone();
+        let (tx, rx) = oneshot::channel();
+        let winner = Arc::new(Mutex::new(Some(tx)));
+        let cancel_winner = winner.clone();
+
+        let id = handle.on_cancel(move || {
+            if let Some(tx) = cancel_winner.lock().expect("error acquiring a lock.").take() {
+                let _ = tx.send(Async::Err(E::from(Cancelled)));
+            }
+        });
+
+        self.on_complete(move |val| {
+            let tx = winner.lock().expect("error acquiring a lock.").take();
+
+            // Resolved first, the callback would only pile up in the scope.
+            handle.unregister(id);
+
+            if let Some(tx) = tx {
+                let _ = tx.send(Async::lift_result(val));
+            }
+        });
+
+        Future::from_async_channel_on(executor, rx)
+    }
+
+    /// Hand the resolved value of this future to `f` on the pool. A `Continue` is
+    /// re-queued behind the future it carries instead of being waited on.
+    fn transform<S, U, F>(self, f: F) -> Future<S, U>
+        where F: FnOnce(Result<T, E>) -> Async<S, U> + Send + 'static,
+              S: Send + 'static,
+              U: Send + 'static
+    {
+        self.process(move |val| match val {
+            Async::Ok(val) => f(Ok(val)),
+            Async::Err(err) => f(Err(err)),
+            Continue(next) => Continue(next.transform(f))
+        })
+    }
+
+    /// Run `f` on `executor`, or the global pool when there's none.
+    fn spawn<F>(executor: Option<FutureExecutor>, f: F) -> Future<T, E>
+        where F: FnOnce() -> Async<T, E> + Send + 'static
+    {
+        let (tx, rx) = oneshot::channel();
+
+        execute(&executor, move || { let _ = tx.send(f()); });
+
+        Future::<T, E> {
+            inner: FutureInner::Channel(rx),
+            executor,
+            waker: None
+        }
+    }
+
+    /// A future for whatever comes through `receiver`, with the combinators chained
+    /// onto it running on `executor` the same as for the future it came from.
+    fn from_async_channel_on(executor: Option<FutureExecutor>, receiver: oneshot::Receiver<Async<T, E>>) -> Future<T, E> {
         Future::<T, E> {
-            receiver: rx,
-            read: false
+            inner: FutureInner::Channel(receiver),
+            executor,
+            waker: None
+        }
+    }
+
+    pub(crate) fn from_oneshot(receiver: oneshot::Receiver<Async<T, E>>) -> Future<T, E> {
+        Future::from_async_channel_on(None, receiver)
+    }
+
+    fn is_never(&self) -> bool {
+        matches!(self.inner, FutureInner::Never)
+    }
+
+    /// Hand whatever this future sends, `Continue` included, to `f` on the pool. If
+    /// nothing has been sent yet `f` is left with the channel, and whoever sends the
+    /// value queues it, so no thread sits waiting and a pool thread never blocks on
+    /// work that may only be submitted after it.
+    fn process<S, U, F>(self, f: F) -> Future<S, U>
+        where F: FnOnce(Async<T, E>) -> Async<S, U> + Send + 'static,
+              S: Send + 'static,
+              U: Send + 'static
+    {
+        let Future { inner, executor, .. } = self;
+
+        let rx = match inner {
+            FutureInner::Ready(val) => return Future::spawn(executor, move || f(Async::lift_result(val))),
+            FutureInner::Channel(rx) => rx,
+            // Nothing will ever come through, so there's nothing to wait for.
+            FutureInner::Never => return Future::never()
+        };
+
+        let (tx, next) = oneshot::channel();
+        let next_executor = executor.clone();
+
+        // A sender that goes away without sending anything drops `tx` along with `f`,
+        // which disconnects the next future the same way.
+        rx.on_value(move |val| {
+            if let Some(val) = val {
+                execute(&next_executor, move || { let _ = tx.send(f(val)); });
+            }
+        });
+
+        Future::from_async_channel_on(executor, next)
+    }
+
+    /// `timeout`, with the timer thread spawned from `builder`.
+    fn timeout_on(self, builder: thread::Builder, dur: Duration, err: E) -> Future<T, E> {
+        let executor = self.executor.clone();
+        let (tx, rx) = oneshot::channel();
+        let tx = Arc::new(Mutex::new(Some(tx)));
+        let timer = tx.clone();
+
+        self.on_complete(move |val| {
+            if let Some(tx) = tx.lock().expect("error acquiring a lock.").take() {
+                let _ = tx.send(Async::lift_result(val));
+            }
+        });
+
+        builder.spawn(move || {
+            thread::sleep(dur);
+
+            if let Some(tx) = timer.lock().expect("error acquiring a lock.").take() {
+                let _ = tx.send(Async::Err(err));
+            }
+        }).expect("error spawning the timeout thread.");
+
+        Future::from_async_channel_on(executor, rx)
+    }
+
+    /// Wait for the result until `deadline`, following any `Continue` along the way.
+    /// `None` means the deadline passed first.
+    fn await_until(self, deadline: Instant) -> Option<Result<T, E>> {
+        let val = match self.inner {
+            FutureInner::Ready(val) => Ok(Async::lift_result(val)),
+            FutureInner::Channel(rx) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            FutureInner::Never => {
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                Err(RecvTimeoutError::Timeout)
+            }
+        };
+
+        match val {
+            Ok(Async::Ok(val)) => Some(Ok(val)),
+            Ok(Async::Err(err)) => Some(Err(err)),
+            Ok(Continue(next)) => next.await_until(deadline),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => panic!("error trying to wait for channel.")
+        }
+    }
+
+    fn retry_after<F>(factory: F, attempts: usize, backoff: Option<Duration>) -> Future<T, E>
+        where F: Fn() -> Future<T, E> + Send + 'static
+    {
+        assert!(attempts > 0, "cannot retry a future with 0 attempts.");
+
+        factory().recover_with(move |err| {
+            if attempts == 1 {
+                return Future::err(err);
+            }
+
+            match backoff {
+                Some(backoff) => {
+                    let (tx, rx) = oneshot::channel();
+
+                    thread::spawn(move || {
+                        thread::sleep(backoff);
+                        let _ = tx.send(Continue(Future::retry_after(factory, attempts - 1, Some(backoff))));
+                    });
+
+                    Future::from_oneshot(rx)
+                },
+                None => Future::retry_after(factory, attempts - 1, None)
+            }
+        })
+    }
+
+    /// Run `f` on the pool with the resolved value of this future once it's
+    /// available, without producing another future for anyone to wait on. Nothing
+    /// waits for the value, it's whoever sends it that queues `f`. When the sender
+    /// goes away without sending anything, or the future never resolves, `f` is
+    /// dropped without running.
+    fn on_complete<F>(self, f: F)
+        where F: FnOnce(Result<T, E>) + Send + 'static
+    {
+        let executor = self.executor.clone();
+
+        self.when_settled(move |val| {
+            if let Some(val) = val {
+                execute(&executor, move || f(val));
+            }
+        });
+    }
+
+    /// Call `f` with the resolved value as soon as it's there, following any
+    /// `Continue` along the way, or with `None` if a sender went away without sending
+    /// anything. `f` runs right away if the value already arrived and otherwise on
+    /// the thread that sends it, so it must be quick and must not block. A future
+    /// that never resolves drops `f`.
+    fn when_settled<F>(self, f: F)
+        where F: FnOnce(Option<Result<T, E>>) + Send + 'static
+    {
+        let mut future = self;
+
+        loop {
+            let rx = match future.inner {
+                FutureInner::Ready(val) => return f(Some(val)),
+                FutureInner::Channel(rx) => rx,
+                FutureInner::Never => return
+            };
+
+            match rx.try_recv() {
+                Ok(Continue(next)) => future = next,
+                Ok(val) => return f(Some(val.into_result())),
+                Err(TryRecvError::Disconnected) => return f(None),
+                Err(TryRecvError::Empty) => {
+                    return rx.on_value(move |val| match val {
+                        Some(Continue(next)) => next.when_settled(f),
+                        Some(val) => f(Some(val.into_result())),
+                        None => f(None)
+                    });
+                }
+            }
+        }
+    }
+
+    /// Feed the result of every future into `step`, along with its index, as soon as
+    /// it resolves. The first `Some` returned by `step` resolves the combined future
+    /// and whatever the other futures send after that is dropped. `futures` must not
+    /// be empty.
+    ///
+    /// Every future sends its result, tagged with its index, down one shared channel
+    /// and a single collector thread blocks on the other end, so gathering thousands
+    /// of futures still costs one thread. A future whose sender went away without
+    /// sending anything never reaches `step`, the same as one that never resolves.
+    /// Once nothing is left to reach it and `step` still hasn't returned `Some`, the
+    /// combined future can't resolve either and continues with `Future::never`.
+    fn gather<S, V, U, G>(futures: Vec<Future<T, E>>, state: S, mut step: G) -> Future<V, U>
+        where S: Send + 'static,
+              V: Send + 'static,
+              U: Send + 'static,
+              G: FnMut(&mut S, usize, Result<T, E>) -> Option<Async<V, U>> + Send + 'static
+    {
+        if futures.iter().all(Future::is_never) {
+            return Future::never();
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let (fan_tx, fan_rx) = channel();
+
+        for (i, future) in futures.into_iter().enumerate() {
+            let fan_tx = fan_tx.clone();
+
+            future.when_settled(move |val| {
+                if let Some(val) = val {
+                    let _ = fan_tx.send((i, val));
+                }
+            });
+        }
+
+        // Only the futures hold on to a sender, so the collector sees the channel
+        // close once every one of them has either sent or gone away.
+        drop(fan_tx);
+
+        thread::spawn(move || {
+            let mut state = state;
+
+            while let Ok((i, val)) = fan_rx.recv() {
+                if let Some(out) = step(&mut state, i, val) {
+                    let _ = tx.send(out);
+                    return;
+                }
+            }
+
+            let _ = tx.send(Continue(Future::never()));
+        });
+
+        Future::from_oneshot(rx)
+    }
+
+    /// Make sure `waker` is woken once a value arrives. The first call swaps the
+    /// receiver for a new one and leaves a callback with the old one, which forwards
+    /// the value and then wakes whichever waker was registered last.
+    fn register_waker(&mut self, waker: &Waker) {
+        if let Some(ref slot) = self.waker {
+            *slot.lock().expect("error acquiring a lock.") = waker.clone();
+            return;
+        }
+
+        let slot = Arc::new(Mutex::new(waker.clone()));
+        let (tx, rx) = oneshot::channel();
+
+        let forward = match mem::replace(&mut self.inner, FutureInner::Channel(rx)) {
+            FutureInner::Channel(forward) => forward,
+            FutureInner::Ready(_) | FutureInner::Never => unreachable!()
+        };
+
+        let wake = slot.clone();
+
+        forward.on_value(move |val| {
+            // Wake even if the sender went away, the next poll reports it.
+            if let Some(val) = val {
+                let _ = tx.send(val);
+            }
+
+            drop(tx);
+            wake.lock().expect("error acquiring a lock.").wake_by_ref();
+        });
+
+        self.waker = Some(slot);
+    }
+
+    /// Queue the next step of `fold`, or send the result once `iter` runs out. A
+    /// step that continues with another future picks up again when that one resolves,
+    /// so a long fold never nests futures inside each other.
+    fn fold_step<I, F, Acc>(mut iter: I, acc: Acc, f: Arc<F>, tx: oneshot::Sender<Async<Acc, E>>)
+        where I: Iterator<Item = T> + Send + 'static,
+              F: Fn(Acc, T) -> Async<Acc, E> + Send + Sync + 'static,
+              Acc: Send + 'static
+    {
+        let item = match iter.next() {
+            Some(item) => item,
+            None => {
+                let _ = tx.send(Async::Ok(acc));
+                return;
+            }
+        };
+
+        global_pool().execute(move || match f(acc, item) {
+            Async::Ok(acc) => Future::fold_step(iter, acc, f, tx),
+            Async::Err(err) => { let _ = tx.send(Async::Err(err)); },
+            Continue(next) => next.on_complete(move |val| match val {
+                Ok(acc) => Future::fold_step(iter, acc, f, tx),
+                Err(err) => { let _ = tx.send(Async::Err(err)); }
+            })
+        });
+    }
+}
+
+// The value, if there is one, is moved out rather than borrowed, so nothing
+// relies on a future staying where it is.
+impl<T, E> Unpin for Future<T, E> {}
+
+/// Lets a `Future` be `.await`ed from async code. Polling never blocks, when the
+/// value isn't there yet the task is woken by whoever sends it once it arrives. `Continue` is followed, so the output is never a `Continue`.
+///
+/// ```edition2018
+/// use tangle::{Async, Future};
+///
+/// async fn double(f: Future<u32>) -> Async<u32, ()> {
+///     match f.await {
+///         Async::Ok(n) => Async::Ok(n * 2),
+///         other => other
+///     }
+/// }
+/// ```
+impl<T, E> StdFuture for Future<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    type Output = Async<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Async<T, E>> {
+        let this = self.get_mut();
+
+        loop {
+            let val = match this.inner {
+                FutureInner::Ready(_) => match mem::replace(&mut this.inner, FutureInner::Never) {
+                    FutureInner::Ready(val) => return Poll::Ready(Async::lift_result(val)),
+                    _ => unreachable!()
+                },
+                // Nothing will ever wake the task, there's no point keeping a waker.
+                FutureInner::Never => return Poll::Pending,
+                FutureInner::Channel(ref rx) => match rx.try_recv() {
+                    Ok(val) => val,
+                    Err(TryRecvError::Empty) => {
+                        this.register_waker(cx.waker());
+                        return Poll::Pending;
+                    },
+                    Err(TryRecvError::Disconnected) => panic!("error trying to wait for channel.")
+                }
+            };
+
+            match val {
+                Continue(next) => {
+                    this.inner = next.inner;
+                    this.waker = None;
+                },
+                val => return Poll::Ready(val)
+            }
+        }
+    }
+}
+
+impl<E> Future<(), E>
+    where E: Send + 'static
+{
+    /// A future that succeeds once `dur` has passed, to put a pause in a chain.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::{Future, Async};
+    ///
+    /// let f: Future<u32> = Future::delay(Duration::from_millis(5)).and_then(|_| Async::Ok(1));
+    /// assert_eq!(f.recv(), Ok(1));
+    /// ```
+    pub fn delay(dur: Duration) -> Future<(), E> {
+        Future::delayed(dur, || Async::Ok(()))
+    }
+}
+
+impl<T> Future<T, T>
+    where T: Send + 'static
+{
+    /// Flip success and failure for a future whose two sides share a type.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let lookup: Future<&str, &str> = Future::err("not found");
+    /// assert_eq!(lookup.swap_result().recv(), Ok("not found"));
+    /// ```
+    pub fn swap_result(self) -> Future<T, T> {
+        self.transform(|val| match val {
+            Ok(val) => Async::Err(val),
+            Err(err) => Async::Ok(err)
+        })
+    }
+}
+
+impl<T, E> Future<Async<T, E>, Never>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// The inverse of `materialize`, turning the `Async` value back into the outcome.
+    pub fn dematerialize(self) -> Future<T, E> {
+        self.transform(|val| match val {
+            Ok(val) => val,
+            Err(never) => match never {}
+        })
+    }
+}
+
+impl<T, E> Future<Future<T, E>, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// Wait for the inner future once the outer one has produced it. The same as
+    /// `and_then` with `Continue`, so neither wait holds up the calling thread.
+    ///
+    /// ```
+    /// use tangle::Future;
+    ///
+    /// let f: Future<Future<u32>> = Future::unit(Future::unit(5));
+    /// assert_eq!(f.flatten().recv(), Ok(5));
+    /// ```
+    pub fn flatten(self) -> Future<T, E> {
+        self.and_then(Continue)
+    }
+}
+
+/// A future whose result can be observed any number of times. Every clone shares
+/// the same underlying computation and gets its own copy of the value.
+#[derive(Debug)]
+pub struct SharedFuture<T, E=()> {
+    inner: Arc<(Mutex<SharedState<T, E>>, Condvar)>
+}
+
+/// The state behind `Future::limit_parallelism`, shared by the futures running.
+struct Limit<T, E, F>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    // Values still to arrive, counting the futures not created yet.
+    remaining: AtomicUsize,
+    values: Mutex<Vec<Option<T>>>,
+    tasks: Mutex<iter::Enumerate<vec::IntoIter<F>>>,
+    // Taken by whoever settles the combined future.
+    tx: Mutex<Option<oneshot::Sender<Async<Vec<T>, E>>>>
+}
+
+impl<T, E, F> Limit<T, E, F>
+    where T: Send + 'static,
+          E: Send + 'static,
+          F: FnOnce() -> Future<T, E> + Send + 'static
+{
+    /// Create the next future, if there's one left, for it to take a free slot.
+    fn submit(limit: &Arc<Limit<T, E, F>>) {
+        let next = limit.tasks.lock().expect("error acquiring a lock.").next();
+
+        let (i, task) = match next {
+            Some(next) => next,
+            None => return
+        };
+
+        let future = task();
+        let executor = future.executor.clone();
+        let limit = limit.clone();
+
+        // Queued rather than run by whoever settles the future, so a long run of
+        // ready futures doesn't nest one submit inside the other.
+        future.when_settled(move |val| execute(&executor, move || limit.settle(i, val)));
+    }
+
+    fn settle(self: Arc<Self>, i: usize, val: Option<Result<T, E>>) {
+        match val {
+            Some(Ok(val)) => {
+                self.values.lock().expect("error acquiring a lock.")[i] = Some(val);
+
+                if self.remaining.fetch_sub(1, Ordering::SeqCst) > 1 {
+                    return Limit::submit(&self);
+                }
+
+                let values = mem::take(&mut *self.values.lock().expect("error acquiring a lock."));
+                self.finish(Async::Ok(values.into_iter().map(|val| val.unwrap()).collect()));
+            },
+            Some(Err(err)) => self.finish(Async::Err(err)),
+            // Disconnect the combined future as well.
+            None => drop(self.tx.lock().expect("error acquiring a lock.").take())
+        }
+    }
+
+    fn finish(&self, val: Async<Vec<T>, E>) {
+        if let Some(tx) = self.tx.lock().expect("error acquiring a lock.").take() {
+            let _ = tx.send(val);
+        }
+    }
+}
+
+impl<T, E, F> Drop for Limit<T, E, F>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    // Only futures that never resolve are left and nothing will be submitted anymore.
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.lock().expect("error acquiring a lock.").take() {
+            let _ = tx.send(Continue(Future::never()));
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SharedState<T, E> {
+    // Along with the futures handed out by `to_future` in the meantime.
+    Pending(Vec<oneshot::Sender<Async<T, E>>>),
+    Done(Result<T, E>),
+    // The source went away without resolving, its sender was dropped say, or panicked.
+    Gone
+}
+
+/// Settles a shared future with `Gone` when dropped, unless it was settled before.
+struct GoneOnDrop<T, E>(Arc<(Mutex<SharedState<T, E>>, Condvar)>)
+    where T: Clone + Send + 'static,
+          E: Clone + Send + 'static;
+
+impl<T, E> GoneOnDrop<T, E>
+    where T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    fn settle(&self, state: SharedState<T, E>) {
+        let (ref lock, ref cvar) = *self.0;
+        let mut current = lock.lock().expect("error acquiring a lock.");
+
+        let waiting = match *current {
+            SharedState::Pending(ref mut waiting) => mem::take(waiting),
+            _ => return
+        };
+
+        if let SharedState::Done(ref val) = state {
+            for tx in waiting {
+                let _ = tx.send(Async::lift_result(val.clone()));
+            }
+        }
+
+        *current = state;
+        cvar.notify_all();
+    }
+}
+
+impl<T, E> Drop for GoneOnDrop<T, E>
+    where T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    fn drop(&mut self) {
+        self.settle(SharedState::Gone);
+    }
+}
+
+impl<T, E> SharedFuture<T, E>
+    where T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    /// Same as `wait`, only needs to be called as `r#await` from the 2018 edition on.
+    pub fn await(&self) -> Async<T, E> {
+        self.wait()
+    }
+
+    /// Block until the value is available and return a copy of it. Panics if the
+    /// source went away without resolving.
+    pub fn wait(&self) -> Async<T, E> {
+        let (ref lock, ref cvar) = *self.inner;
+        let mut state = lock.lock().expect("error acquiring a lock.");
+
+        loop {
+            match *state {
+                SharedState::Pending(_) => state = cvar.wait(state).expect("error acquiring a lock."),
+                SharedState::Done(ref val) => return Async::lift_result(val.clone()),
+                SharedState::Gone => panic!("the shared future went away without resolving.")
+            }
+        }
+    }
+
+    /// A regular future resolving to a copy of the shared value. It's disconnected,
+    /// like the future of a dropped promise, if the source went away.
+    pub fn to_future(&self) -> Future<T, E> {
+        let (ref lock, _) = *self.inner;
+        let (tx, rx) = oneshot::channel();
+
+        match *lock.lock().expect("error acquiring a lock.") {
+            SharedState::Pending(ref mut waiting) => waiting.push(tx),
+            SharedState::Done(ref val) => { let _ = tx.send(Async::lift_result(val.clone())); },
+            SharedState::Gone => {}
+        }
+
+        Future::from_oneshot(rx)
+    }
+
+    /// Whether the value is there yet, so `wait` would return right away. Also true
+    /// once the source went away, when `wait` panics right away instead.
+    pub fn is_ready(&self) -> bool {
+        let (ref lock, _) = *self.inner;
+
+        match *lock.lock().expect("error acquiring a lock.") {
+            SharedState::Pending(_) => false,
+            SharedState::Done(_) | SharedState::Gone => true
+        }
+    }
+
+    /// Whether the source went away without resolving, so the value never comes.
+    pub fn is_gone(&self) -> bool {
+        let (ref lock, _) = *self.inner;
+
+        matches!(*lock.lock().expect("error acquiring a lock."), SharedState::Gone)
+    }
+}
+
+impl<T, E> From<Future<T, E>> for SharedFuture<T, E>
+    where T: Clone + Send + 'static,
+          E: Clone + Send + 'static
+{
+    fn from(future: Future<T, E>) -> SharedFuture<T, E> {
+        future.shared()
+    }
+}
+
+impl<T, E> Clone for SharedFuture<T, E> {
+    fn clone(&self) -> SharedFuture<T, E> {
+        SharedFuture { inner: self.inner.clone() }
+    }
+}
+
+/// Collects the options for a future in one place before starting it. Retries run
+/// inside the timeout, so the deadline covers every attempt rather than each one.
+///
+/// ```
+/// use std::time::Duration;
+/// use tangle::{FutureBuilder, Async};
+///
+/// let f = FutureBuilder::<u32, &str>::new()
+///     .retry(2)
+///     .timeout(Duration::from_secs(5), "timed out")
+///     .label("lookup")
+///     .build(|| Async::Ok(5));
+///
+/// assert_eq!(f.recv(), Ok(5));
+/// ```
+#[derive(Debug)]
+pub struct FutureBuilder<T, E=()> {
+    timeout: Option<(Duration, E)>,
+    retries: usize,
+    executor: Option<FutureExecutor>,
+    label: Option<String>,
+    marker: PhantomData<T>
+}
+
+impl<T, E> FutureBuilder<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    pub fn new() -> FutureBuilder<T, E> {
+        FutureBuilder {
+            timeout: None,
+            retries: 0,
+            executor: None,
+            label: None,
+            marker: PhantomData
+        }
+    }
+
+    /// Fail with `err` if the future, retries included, hasn't resolved after `dur`.
+    pub fn timeout(mut self, dur: Duration, err: E) -> FutureBuilder<T, E> {
+        self.timeout = Some((dur, err));
+        self
+    }
+
+    /// Run the closure again, up to `n` more times, for as long as it keeps failing.
+    pub fn retry(mut self, n: usize) -> FutureBuilder<T, E> {
+        self.retries = n;
+        self
+    }
+
+    /// Run the closure, and every retry of it, on `executor` instead of the global
+    /// pool.
+    pub fn pool(mut self, executor: &FutureExecutor) -> FutureBuilder<T, E> {
+        self.executor = Some(executor.clone());
+        self
+    }
+
+    /// Name of the task. The closure itself runs on a pool thread, which is shared
+    /// with other futures and keeps its own name, so the label is exposed to it, and
+    /// every retry of it, through `current_label` instead. Helper threads the future
+    /// needs, such as the timeout timer, are named after it.
+    pub fn label(mut self, name: &str) -> FutureBuilder<T, E> {
+        self.label = Some(name.to_string());
+        self
+    }
+
+    pub fn build<F>(self, f: F) -> Future<T, E>
+        where F: Fn() -> Async<T, E> + Send + Sync + 'static
+    {
+        let label = self.label.as_ref().map(|label| Arc::from(label.as_str()));
+        let future = FutureBuilder::attempt(self.executor, label, Arc::new(f), self.retries);
+
+        let (dur, err) = match self.timeout {
+            Some(timeout) => timeout,
+            None => return future
+        };
+
+        let mut builder = thread::Builder::new();
+
+        if let Some(label) = self.label {
+            builder = builder.name(label);
+        }
+
+        future.timeout_on(builder, dur, err)
+    }
+
+    fn attempt<F>(executor: Option<FutureExecutor>, label: Option<Arc<str>>, f: Arc<F>, retries: usize) -> Future<T, E>
+        where F: Fn() -> Async<T, E> + Send + Sync + 'static
+    {
+        let run = f.clone();
+        let task_label = label.clone();
+
+        Future::spawn(executor.clone(), move || {
+            let previous = LABEL.with(|current| current.replace(task_label));
+            let _restore = LabelGuard(previous);
+
+            run()
+        }).transform(move |val| match val {
+            Ok(val) => Async::Ok(val),
+            Err(_) if retries > 0 => Continue(FutureBuilder::attempt(executor, label, f, retries - 1)),
+            Err(err) => Async::Err(err)
+        })
+    }
+}
+
+/// Puts back the label of whatever ran on the thread before, even if the closure
+/// panics.
+struct LabelGuard(Option<Arc<str>>);
+
+impl Drop for LabelGuard {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        LABEL.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+impl<T, E> Default for FutureBuilder<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    fn default() -> FutureBuilder<T, E> {
+        FutureBuilder::new()
+    }
+}
+
+/// The error of a future whose result was no longer wanted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "future was cancelled")
+    }
+}
+
+/// Cancels the future it came from when dropped, see `Future::with_cancel_on_drop`.
+#[derive(Debug)]
+pub struct CancelOnDrop {
+    token: CancellationToken
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: bool,
+    next_id: usize,
+    callbacks: Vec<(usize, Box<dyn FnOnce() + Send>)>
+}
+
+/// The cancelling half of a cancellation scope. Clones share the scope, cancelling
+/// any one of them cancels every future watching one of its handles.
+#[derive(Clone)]
+pub struct CancellationToken {
+    state: Arc<Mutex<CancelState>>
+}
+
+impl CancellationToken {
+    /// ```
+    /// use tangle::CancellationToken;
+    ///
+    /// let (token, handle) = CancellationToken::new();
+    /// assert!(!handle.is_cancelled());
+    ///
+    /// token.cancel();
+    /// assert!(handle.is_cancelled());
+    /// ```
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> (CancellationToken, CancellationHandle) {
+        let state = Arc::new(Mutex::new(CancelState::default()));
+
+        (CancellationToken { state: state.clone() }, CancellationHandle { state })
+    }
+
+    /// Cancel the scope. Only the first call has any effect.
+    pub fn cancel(&self) {
+        let callbacks = {
+            let mut state = self.state.lock().expect("error acquiring a lock.");
+
+            if state.cancelled {
+                return;
+            }
+
+            state.cancelled = true;
+            mem::take(&mut state.callbacks)
+        };
+
+        for (_, f) in callbacks {
+            f();
+        }
+    }
+
+    /// A new handle watching the same scope.
+    pub fn handle(&self) -> CancellationHandle {
+        CancellationHandle { state: self.state.clone() }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken").finish()
+    }
+}
+
+/// The watching half of a cancellation scope, see `Future::with_cancellation`.
+#[derive(Clone)]
+pub struct CancellationHandle {
+    state: Arc<Mutex<CancelState>>
+}
+
+impl CancellationHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.state.lock().expect("error acquiring a lock.").cancelled
+    }
+
+    /// Run `f` once the scope is cancelled, right away if it already is. The id is
+    /// for taking `f` back with `unregister` once it's no longer needed.
+    fn on_cancel<F>(&self, f: F) -> usize
+        where F: FnOnce() + Send + 'static
+    {
+        let mut state = self.state.lock().expect("error acquiring a lock.");
+        let id = state.next_id;
+
+        state.next_id += 1;
+
+        if state.cancelled {
+            drop(state);
+            f();
+        } else {
+            state.callbacks.push((id, Box::new(f)));
+        }
+
+        id
+    }
+
+    /// Drop the callback registered under `id`, if it hasn't run yet.
+    fn unregister(&self, id: usize) {
+        self.state.lock().expect("error acquiring a lock.").callbacks.retain(|&(other, _)| other != id);
+    }
+}
+
+impl fmt::Debug for CancellationHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationHandle").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+/// One of two values, see `Future::select`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R)
+}
+
+/// Which future won a `race!`. Variants past the number of raced futures can't
+/// occur.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaceResult<A, B, C=Never, D=Never> {
+    First(A),
+    Second(B),
+    Third(C),
+    Fourth(D)
+}
+
+/// The error of a future that followed more `Continue`s than it was allowed to, see
+/// `Future::with_recursion_limit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecursionLimitExceeded;
+
+impl fmt::Display for RecursionLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "continuation depth limit exceeded")
+    }
+}
+
+/// What `Future::probe` reports about the future it's watching.
+#[derive(Debug)]
+pub enum ProbeEvent<T, E> {
+    Started,
+    Completed(Async<T, E>)
+}
+
+/// Block until every future has resolved, returning the results in input order.
+///
+/// ```
+/// use tangle::{Future, block_on_all};
+///
+/// let results = block_on_all(vec![Future::<u32>::unit(1), Future::unit(2)]);
+/// assert_eq!(results.len(), 2);
+/// ```
+pub fn block_on_all<T, E>(futures: Vec<Future<T, E>>) -> Vec<Async<T, E>>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    futures.into_iter().map(|future| future.wait()).collect()
+}
+
+/// Block until the first of `futures` resolves and return its result, whether it
+/// succeeded or not. Panics if `futures` is empty.
+pub fn block_on_first<T, E>(futures: Vec<Future<T, E>>) -> Async<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    assert!(!futures.is_empty(), "`block_on_first` needs at least one future.");
+
+    Future::gather(futures, (), |_, _, val| Some(Async::lift_result(val))).wait()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::channel;
+    use std::task::Poll;
+
+    #[test]
+    fn async_macro() {
+        fn foo() -> Async<u32, ()> {
+            let v = Ok(123);
+            let v = async!(v);
+            assert_eq!(v, 123);
+            Async::Ok(v)
+        }
+
+        foo();
+    }
+
+    #[test]
+    fn async_macro_err() {
+        fn foo() -> Async<u32, u32> {
+            let v: Result<u32, u32> = Err(123);
+            async!(v);
+            Async::Ok(0)
+        }
+
+        assert!(foo().is_err());
+    }
+
+    #[test]
+    fn async_map() {
+        let val: Async<u32, ()> = Async::Ok(123);
+        match val.map(|x| x * 2) {
+            Async::Ok(v) => assert_eq!(v, 246),
+            _ => panic!("Unexpected error.")
+        }
+    }
+
+    #[test]
+    fn async_map_continue() {
+        let val: Async<u32, ()> = Async::Continue(future! {
+            Async::Ok(123)
+        });
+
+        assert_eq!(val.map(|x| x * 2).to_future().recv(), Ok(246));
+    }
+
+    #[test]
+    fn async_map_err_continue() {
+        let val: Async<(), u32> = Async::Continue(Future::err(1));
+
+        assert_eq!(val.map_err(|x| x + 5).to_future().recv(), Err(6));
+    }
+
+    #[test]
+    fn async_map_err() {
+        let val: Async<(), u32> = Async::Err(1);
+        match val.map_err(|x| x + 5) {
+            Async::Err(e) => assert_eq!(e, 6),
+            _ => panic!("Unexpected error")
+        }
+    }
+
+    #[test]
+    fn create_channel() {
+        let (tx, future) = Future::<u32>::channel();
+
+        tx.send(123).unwrap();
+
+        match future.recv() {
+            Ok(v) => assert_eq!(v, 123),
+            Err(_) => panic!("Unexpected case.")
+        }
+    }
+
+    #[test]
+    fn from_chan1() {
+        let (tx, rx) = channel();
+        let f: Future<u32> = Future::from_channel(rx);
+
+        let f = f.map(|n| {
+            assert_eq!(n, 555);
+            n + 5
+        }).map(|n| {
+            assert_eq!(n, 560);
+            n
+        });
+
+        // Resolve the future through the sender half of the channel.
+        tx.send(555).unwrap();
+
+        assert_eq!(f.recv().unwrap(), 560);
+    }
+
+    #[test]
+    fn to_future_macro() {
+        future! {
+            if true {
+                Async::Ok(123)
+            } else {
+                Async::Err(5)
+            }
+        };
+    }
+
+    #[test]
+    fn test_async() {
+        let f: Future<usize> = future! { Async::Ok(5) };
+
+        let next = f.and_then(|n| {
+            thread::sleep(Duration::from_millis(50));
+
+            Continue(Future::new(move || {
+                thread::sleep(Duration::from_millis(100));
+                Async::Ok(n * 100)
+            }))
+        });
+
+        match next.recv() {
+            Ok(n) => assert_eq!(n, 500),
+            Err(_) => panic!("Unexpected value")
+        }
+    }
+
+    #[test]
+    fn resolve_from_value() {
+        let val: Future<usize> = Future::unit(5);
+
+        match val.recv() {
+            Ok(n) => assert_eq!(n, 5),
+            Err(_) => panic!("Unexpected value")
+        }
+    }
+
+    #[test]
+    fn map_promise() {
+        let count: Future<usize> = Future::unit(5);
+
+        let curr: Future<usize> = count.and_then(|_| {
+            Continue(future! {
+                Async::Ok(100)
+            })
+        });
+
+        match curr.recv() {
+            Ok(n) => assert_eq!(n, 100),
+            Err(_) => panic!("Unexpected value")
+        }
+    }
+
+    #[test]
+    fn promise_map_before_resolve() {
+        let mut p = Promise::<u32, ()>::new();
+        let f = p.future().map(|n| n + 1);
+
+        let mut p = p.map_before_resolve(|s: &str| s.parse().unwrap());
+        p.resolve("41");
+
+        assert_eq!(f.recv().unwrap(), 42);
+    }
+
+    #[test]
+    fn promise_map_before_resolve_settled() {
+        let mut p = Promise::<u32, ()>::new();
+        p.resolve(1);
+
+        let mut p = p.map_before_resolve(|s: &str| s.len() as u32);
+
+        assert_eq!(p.state, PromiseState::Resolved);
+        assert_eq!(p.try_resolve("two"), Err(AlreadyResolvedError));
+    }
+
+    #[test]
+    fn promise_map_before_resolve_own_future() {
+        let p = Promise::<u32, ()>::new();
+        let mut p = p.map_before_resolve(|s: &str| s.len() as u32);
+        let f = p.future();
+
+        p.resolve("unmapped");
+
+        assert_eq!(f.recv(), Ok("unmapped"));
+    }
+
+    #[test]
+    fn flat_map_err_ok() {
+        let f: Future<u32, ()> = Future::unit(1);
+        let f: Future<u32, u32> = f.flat_map_err(|_| Future::err(5));
+
+        assert_eq!(f.recv(), Ok(1));
+    }
+
+    #[test]
+    fn flat_map_err_fallback() {
+        let f: Future<u32, ()> = future! { Async::Err(()) };
+        let f: Future<u32, u32> = f.flat_map_err(|_| Future::new(|| {
+            thread::sleep(Duration::from_millis(10));
+            Async::Err(5)
+        })).and_then(|n| Async::Ok(n + 1));
+
+        assert_eq!(f.recv(), Err(5));
+    }
+
+    #[test]
+    fn reduce_err_first_success() {
+        let futures: Vec<Future<u32, u32>> = vec![
+            Future::err(1),
+            Future::new(|| {
+                thread::sleep(Duration::from_millis(20));
+                Async::Err(2)
+            }),
+            Future::unit(3)
+        ];
+
+        assert_eq!(Future::reduce_err(futures).recv(), Ok(3));
+    }
+
+    #[test]
+    fn reduce_err_all_failed() {
+        let futures: Vec<Future<u32, u32>> = vec![
+            Future::new(|| {
+                thread::sleep(Duration::from_millis(20));
+                Async::Err(1)
+            }),
+            Future::err(2),
+            Future::err(3)
+        ];
+
+        assert_eq!(Future::reduce_err(futures).recv(), Err(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn map_result_err() {
+        let f: Future<u32, u32> = Future::err(5);
+        let f: Future<u32, String> = f.map_result(|res| res.or(Ok(0)).map(|n| n + 1));
+
+        assert_eq!(f.recv(), Ok(1));
+    }
+
+    #[test]
+    fn for_each_parallel_all() {
+        let (tx, rx) = channel();
+
+        let done: Future<(), ()> = Future::for_each_parallel(vec![1, 2, 3], move |n| {
+            let tx = tx.clone();
+            Future::new(move || {
+                tx.send(n).unwrap();
+                Async::Ok(())
+            })
+        });
+
+        assert!(done.recv().is_ok());
+
+        let mut seen = rx.try_iter().collect::<Vec<u32>>();
+        seen.sort();
+        assert_eq!(seen, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn for_each_parallel_err() {
+        let done = Future::for_each_parallel(vec![1, 2, 3], |n| {
+            if n == 2 { Future::err(n) } else { Future::unit(()) }
+        });
+
+        assert_eq!(done.recv(), Err(2));
+    }
+
+    #[test]
+    fn into_option_ok() {
+        let f: Future<u32> = Future::unit(5);
+        assert_eq!(f.into_option().recv(), Ok(Some(5)));
+    }
+
+    #[test]
+    fn async_poll_roundtrip() {
+        let val: Async<u32, ()> = Async::from_poll(Poll::Ready(Ok(5))).unwrap();
+        assert_eq!(val.into_poll(), Poll::Ready(Ok(5)));
+
+        let val: Async<u32, u32> = Async::from_poll(Poll::Ready(Err(1))).unwrap();
+        assert_eq!(val.into_poll(), Poll::Ready(Err(1)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn async_into_poll_continue() {
+        let val: Async<u32, ()> = Async::Continue(Future::unit(5));
+        let _ = val.into_poll();
+    }
+
+    #[test]
+    fn await_with_handler_continue() {
+        let f: Future<usize> = Future::unit(5).and_then(|n| Continue(Future::unit(n * 2)));
+        let caller = thread::current().id();
+        let mut seen = None;
+
+        f.await_with_handler(|val| {
+            assert_eq!(thread::current().id(), caller);
+            seen = Some(val.unwrap());
+        });
+
+        assert_eq!(seen, Some(10));
+    }
+
+    #[test]
+    fn ensure_or_recover_passes() {
+        let f: Future<u32> = Future::unit(5);
+        let f = f.ensure_or_recover(|&n| n > 1, |_| panic!("should not recover"));
+
+        assert_eq!(f.recv(), Ok(5));
+    }
+
+    #[test]
+    fn ensure_or_recover_err() {
+        let f: Future<u32, u32> = Future::err(3);
+        let f = f.ensure_or_recover(|_| false, |_| Future::unit(1));
+
+        assert_eq!(f.recv(), Err(3));
+    }
+
+    #[test]
+    fn builder_retry() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+
+        let f = FutureBuilder::<usize, usize>::new().retry(3).build(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            if n < 2 { Async::Err(n) } else { Async::Ok(n) }
+        });
+
+        assert_eq!(f.recv(), Ok(2));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn builder_retry_exhausted() {
+        let f = FutureBuilder::<usize, &str>::new().retry(1).build(|| Async::Err("nope"));
+
+        assert_eq!(f.recv(), Err("nope"));
+    }
+
+    #[test]
+    fn builder_timeout() {
+        let f = FutureBuilder::<usize, &str>::new()
+            .timeout(Duration::from_millis(10), "timeout")
+            .pool(&FutureExecutor::new(Arc::new(FuturePool::new(1))))
+            .build(|| {
+                thread::sleep(Duration::from_millis(200));
+                Async::Ok(1)
+            });
+
+        assert_eq!(f.recv(), Err("timeout"));
+    }
+
+    #[test]
+    fn builder_label_leaves_pool_threads() {
+        let f = FutureBuilder::<Option<String>, &str>::new()
+            .timeout(Duration::from_secs(5), "timeout")
+            .label("lookup")
+            .build(|| Async::Ok(thread::current().name().map(String::from)));
+
+        assert_ne!(f.recv().unwrap().as_deref(), Some("lookup"));
+    }
+
+    #[test]
+    fn builder_label_without_timeout() {
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let seen = attempts.clone();
+
+        let f = FutureBuilder::<u32, u32>::new()
+            .retry(1)
+            .label("lookup")
+            .build(move || {
+                let mut seen = seen.lock().unwrap();
+                seen.push(current_label());
+
+                if seen.len() == 1 { Async::Err(1) } else { Async::Ok(2) }
+            });
+
+        assert_eq!(f.recv(), Ok(2));
+        assert_eq!(*attempts.lock().unwrap(), vec![Some("lookup".to_string()); 2]);
+
+        let unlabelled = FutureBuilder::<Option<String>>::new().build(|| Async::Ok(current_label()));
+        assert_eq!(unlabelled.recv(), Ok(None));
+    }
+
+    #[test]
+    fn async_lift() {
+        assert_eq!(Async::lift_option(Some(1), || 0).unwrap(), 1);
+        assert!(Async::<u32, u32>::lift_result(Err(2)).is_err());
+        assert_eq!(Async::<u32, u32>::lift_bool(true, 1, 2).unwrap(), 1);
+        assert!(Async::<u32, u32>::lift_bool(false, 1, 2).is_err());
+    }
+
+    #[test]
+    fn sequence_results_indexed_order() {
+        let futures: Vec<Future<u32, u32>> = vec![
+            Future::new(|| {
+                thread::sleep(Duration::from_millis(20));
+                Async::Err(0)
+            }),
+            Future::unit(1),
+            Future::err(2)
+        ];
+
+        let results = Future::sequence_results_indexed(futures).recv().unwrap();
+        assert_eq!(results, vec![(0, Err(0)), (1, Ok(1)), (2, Err(2))]);
+    }
+
+    #[test]
+    fn and_then_flatten_levels() {
+        let f: Future<u32, u32> = Future::new(|| Async::Ok(1));
+
+        let f = f.and_then_flatten(|a| Future::new(move || {
+            Async::Ok(Future::new(move || Async::Ok(a + 10)).map(|b| b + 100))
+        }));
+
+        assert_eq!(f.recv(), Ok(111));
+    }
+
+    #[test]
+    fn and_then_flatten_err() {
+        let f: Future<u32, u32> = Future::unit(1);
+        let outer = f.and_then_flatten::<u32, _>(|_| Future::err(5));
+
+        assert_eq!(outer.recv(), Err(5));
+
+        let f: Future<u32, u32> = Future::unit(1);
+        let inner = f.and_then_flatten(|_| Future::unit(Future::<u32, u32>::err(6)));
+
+        assert_eq!(inner.recv(), Err(6));
+    }
+
+    #[test]
+    fn promise_all_reject() {
+        let (mut resolvers, all) = Promise::<u32, u32>::all(vec![Promise::new(), Promise::new()]);
+
+        let second = resolvers.pop().unwrap();
+        thread::spawn(move || second.reject(7));
+
+        assert_eq!(all.recv(), Err(7));
+    }
+
+    #[test]
+    fn promise_all_empty() {
+        let (resolvers, all) = Promise::<u32>::all(Vec::new());
+
+        assert!(resolvers.is_empty());
+        assert_eq!(all.recv(), Ok(vec![]));
+    }
+
+    #[test]
+    fn probe_events() {
+        let (tx, rx) = channel();
+        let a: Future<u32, u32> = Future::unit(1).probe(tx.clone());
+        let b: Future<u32, u32> = Future::err(2).probe(tx);
+
+        assert_eq!(a.recv(), Ok(1));
+        assert_eq!(b.recv(), Err(2));
+
+        let events = rx.iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events.iter().filter(|e| {
+            matches!(**e, ProbeEvent::Completed(Async::Ok(1)) | ProbeEvent::Completed(Async::Err(2)))
+        }).count(), 2);
+    }
+
+    #[test]
+    fn async_transpose() {
+        let val: Async<Option<u32>, u32> = Async::Ok(Some(1));
+        assert_eq!(val.transpose().unwrap().unwrap(), 1);
+
+        let val: Async<Option<u32>, u32> = Async::Err(2);
+        assert!(val.transpose().unwrap().is_err());
+
+        let val: Async<Option<u32>, u32> = Async::Continue(Future::unit(Some(3)));
+        match val.transpose() {
+            Some(Async::Continue(f)) => assert_eq!(f.recv(), Ok(3)),
+            _ => panic!("Unexpected value")
+        }
+    }
+
+    #[test]
+    fn swap_result_ok() {
+        let f: Future<u32, u32> = Future::unit(1);
+        assert_eq!(f.swap_result().recv(), Err(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Future failed: 5")]
+    fn await_or_panic_err() {
+        let f: Future<u32, u32> = Future::err(5);
+        f.await_or_panic();
+    }
+
+    #[test]
+    fn block_on_all_order() {
+        let futures: Vec<Future<u32, u32>> = vec![
+            Future::new(|| {
+                thread::sleep(Duration::from_millis(20));
+                Async::Ok(1)
+            }),
+            Future::err(2)
+        ];
+
+        let results = block_on_all(futures);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn block_on_first_fastest() {
+        let mut pending = Promise::<u32, u32>::new();
+        let futures = vec![pending.future(), Future::err(2)];
+
+        assert!(block_on_first(futures).is_err());
+    }
+
+    #[test]
+    fn async_recover_or_default() {
+        let futures: Vec<Future<usize, ()>> = vec![Future::unit(2), Future::err(()), Future::unit(3)];
+        let sum = futures.into_iter().map(|f| f.wait().recover_or_default()).sum::<usize>();
+
+        assert_eq!(sum, 5);
+    }
+
+    #[test]
+    fn run_to_completion_waits() {
+        let (tx, rx) = channel();
+
+        Future::<(), ()>::new(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(1).unwrap();
+            Async::Err(())
+        }).run_to_completion();
+
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn run_to_completion_panics() {
+        Future::<(), ()>::new(|| panic!("boom")).run_to_completion();
+    }
+
+    #[test]
+    fn map_continue_resolved() {
+        let f: Future<u32> = Future::unit(1);
+        let f = f.map_continue(|_| panic!("not a continuation"));
+
+        assert_eq!(f.recv(), Ok(1));
+    }
+
+    #[test]
+    fn log_on_err_only_errors() {
+        let (tx, rx) = channel();
+        let logger = move |err: &u32| tx.send(*err).unwrap();
+
+        let ok: Future<u32, u32> = Future::unit(1);
+        assert_eq!(ok.log_on_err(logger.clone()).recv(), Ok(1));
+
+        let err: Future<u32, u32> = Future::err(2);
+        assert_eq!(err.log_on_err(logger).recv(), Err(2));
+
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn with_stack_size_composes() {
+        let f: Future<u32> = Future::with_stack_size(1024 * 1024, || Async::Ok(1));
+
+        assert_eq!(f.map(|n| n + 1).recv(), Ok(2));
+    }
+
+    #[test]
+    fn map_while_ok_aborts() {
+        let mut blocked = Promise::<u32, u32>::new();
+        let mut pending = Some(blocked.future());
+
+        let f = Future::map_while_ok(vec![0, 1, 2, 3], move |n| {
+            match n {
+                0 => pending.take().unwrap(),
+                1 | 2 => Future::err(n),
+                _ => Future::unit(n)
+            }
+        }, 1);
+
+        let (values, errors) = f.recv().unwrap();
+        assert_eq!(errors, vec![1, 2]);
+        assert!(values.len() <= 1);
+    }
+
+    #[test]
+    fn map_while_ok_all() {
+        let f = Future::map_while_ok(vec![1, 2, 3], Future::<u32, u32>::unit, 0);
+
+        assert_eq!(f.recv(), Ok((vec![1, 2, 3], vec![])));
+    }
+
+    #[test]
+    fn as_option_or_log_logs() {
+        let (tx, rx) = channel();
+
+        let f: Future<u32, u32> = Future::err(4);
+        assert_eq!(f.as_option_or_log(move |err| tx.send(*err).unwrap()).recv(), Ok(None));
+        assert_eq!(rx.recv(), Ok(4));
+
+        let f: Future<u32, u32> = Future::unit(1);
+        assert_eq!(f.as_option().recv(), Ok(Some(1)));
+    }
+
+    #[test]
+    fn await_n_quorum() {
+        let mut slow = Promise::<u32, u32>::new();
+        let futures = vec![Future::unit(1), slow.future(), Future::unit(3)];
+
+        let mut quorum = Future::await_n(futures, 2).recv().unwrap();
+        quorum.sort();
+
+        assert_eq!(quorum, vec![1, 3]);
+    }
+
+    #[test]
+    fn await_n_impossible() {
+        let mut slow = Promise::<u32, u32>::new();
+        let futures = vec![Future::err(1), slow.future(), Future::err(2)];
+        let quorum = Future::await_n(futures, 2);
+
+        thread::sleep(Duration::from_millis(10));
+        slow.reject(3);
+
+        assert_eq!(quorum.recv(), Err(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot wait for 3 successes out of 2 futures.")]
+    fn await_n_more_than_len() {
+        Future::await_n(vec![Future::<u32, u32>::unit(1), Future::unit(2)], 3);
+    }
+
+    #[test]
+    fn async_collect_results() {
+        let all: Async<Vec<u32>, Vec<u32>> = (1..4).map(Async::Ok).collect();
+        assert_eq!(all.unwrap(), vec![1, 2, 3]);
+
+        let some: Async<Vec<u32>, Vec<u32>> = (1..4).map(|n| {
+            if n % 2 == 1 { Async::Err(n) } else { Async::Ok(n) }
+        }).collect();
+
+        match some {
+            Async::Err(errors) => assert_eq!(errors, vec![1, 3]),
+            _ => panic!("Unexpected value")
+        }
+    }
+
+    #[test]
+    fn apply_all_err() {
+        let ok: Future<fn(u32) -> u32, u32> = Future::unit(|n| n + 1);
+        let err: Future<fn(u32) -> u32, u32> = Future::err(9);
+
+        assert_eq!(Future::apply_all(vec![ok, err], 1).recv(), Err(9));
+    }
+
+    #[test]
+    #[should_panic(expected = "the shared future went away without resolving.")]
+    fn shared_dropped_promise() {
+        let mut promise = Promise::<u32>::new();
+        let shared = promise.future().shared();
+        let waiter = shared.clone();
+
+        assert!(!shared.is_ready());
+        drop(promise);
+
+        assert!(shared.is_gone());
+        assert!(shared.is_ready());
+        waiter.wait();
+    }
+
+    #[test]
+    fn shared_to_future_pending() {
+        let mut promise = Promise::<u32>::new();
+        let shared = promise.future().shared();
+        let f = shared.to_future();
+
+        promise.resolve(3);
+
+        assert_eq!(f.recv(), Ok(3));
+        assert_eq!(shared.to_future().recv(), Ok(3));
+    }
+
+    #[test]
+    fn shared_many_consumers() {
+        let f: Future<u32> = Future::new(|| {
+            thread::sleep(Duration::from_millis(10));
+            Async::Ok(7)
+        });
+
+        let shared = f.shared();
+        let handles = (0..3).map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || shared.wait().unwrap())
+        }).collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 7);
+        }
+
+        assert_eq!(shared.to_future().map(|n| n + 1).recv(), Ok(8));
+    }
+
+    #[test]
+    fn as_unit() {
+        let val: Async<u32, u32> = Async::Err(1);
+        assert!(val.as_unit().is_err());
+
+        let f: Future<u32, u32> = Future::err(2);
+        assert_eq!(f.as_unit().recv(), Err(2));
+    }
+
+    #[test]
+    fn materialize_roundtrip() {
+        let f: Future<u32, u32> = Future::err(3);
+        assert_eq!(f.materialize().dematerialize().recv(), Err(3));
+
+        let f: Future<u32, u32> = Future::unit(4);
+        assert_eq!(f.materialize().map(|val| val.map(|n| n * 2)).dematerialize().recv(), Ok(8));
+    }
+
+    #[test]
+    fn measure_until_polls() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+
+        let f: Future<usize, &str> = Future::measure_until(Duration::from_millis(1), Duration::from_secs(5), "timeout", move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            if n == 3 { Some(Async::Ok(n)) } else { None }
+        });
+
+        assert_eq!(f.recv(), Ok(3));
+        assert_eq!(polls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn measure_until_timeout() {
+        let f: Future<u32, &str> = Future::measure_until(Duration::from_millis(1), Duration::from_millis(10), "timeout", || None);
+
+        assert_eq!(f.recv(), Err("timeout"));
+    }
+
+    #[test]
+    fn chain_if_ok_branches() {
+        let f: Future<u32, u32> = Future::unit(1);
+        assert_eq!(f.chain_if_ok(|n| Async::Ok(n + 1), |_| Async::Ok(0)).recv(), Ok(2));
+
+        let f: Future<u32, u32> = Future::unit(1);
+        let f = f.branch_result(|res| match res {
+            Ok(n) => Async::Err(n),
+            Err(e) => Async::Ok(e)
+        });
+        assert_eq!(f.recv(), Err(1));
+    }
+
+    #[test]
+    fn async_to_future() {
+        let val: Async<u32, u32> = Async::Err(3);
+        assert_eq!(val.to_future().recv(), Err(3));
+
+        let val: Async<u32, u32> = Continue(Future::unit(4));
+        assert_eq!(val.to_future().recv(), Ok(4));
+    }
+
+    #[test]
+    fn unzip_with_err() {
+        let f: Future<u32, &str> = Future::err("failed");
+        let (a, b) = f.unzip_with(|n| (n, n * 2));
+
+        assert_eq!(a.recv(), Err("failed"));
+        assert_eq!(b.recv(), Err("failed"));
+    }
+
+    #[test]
+    fn aggregate_input_order() {
+        let slow: Future<u32, ()> = Future::with_stack_size(64 * 1024, || {
+            thread::sleep(Duration::from_millis(20));
+            Async::Ok(1)
+        });
+        let futures = vec![slow, Future::unit(2), Future::unit(3)];
+        let digits = Future::aggregate(futures, 0, |acc, n| acc * 10 + n);
+
+        assert_eq!(digits.recv(), Ok(123));
+    }
+
+    #[test]
+    fn aggregate_err() {
+        let futures: Vec<Future<u32, u32>> = vec![Future::unit(1), Future::err(2)];
+
+        assert_eq!(Future::aggregate(futures, 0, |acc, n| acc + n).recv(), Err(2));
+    }
+
+    #[test]
+    fn fail_after_too_many() {
+        let futures: Vec<Future<u32, u32>> = vec![Future::err(1), Future::unit(2), Future::err(3)];
+
+        assert!(Future::fail_after(1, futures).recv().is_err());
+    }
+
+    #[test]
+    fn promise_when_cancelled_not_called() {
+        let called = Arc::new(AtomicUsize::new(0));
+        let counter = called.clone();
+        let mut p = Promise::<u32>::new();
+        let f = p.future();
+
+        p.when_cancelled(move || { counter.fetch_add(1, Ordering::SeqCst); });
+        p.resolve(1);
+
+        assert_eq!(f.recv(), Ok(1));
+        assert_eq!(called.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn then_err_same_type() {
+        let f: Future<u32, String> = Future::err("failed".to_string());
+        let f = f.then_ok(|n| n + 1).then_err(|e| format!("request {}", e));
+
+        assert_eq!(f.recv(), Err("request failed".to_string()));
+    }
+
+    #[test]
+    fn limit_parallelism_bounded() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let tasks = (0..6).map(|n| {
+            let running = running.clone();
+            let peak = peak.clone();
+
+            move || Future::new(move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Async::Ok(n)
+            })
+        }).collect::<Vec<_>>();
+
+        let f: Future<Vec<u32>> = Future::limit_parallelism(tasks, 2);
+
+        assert_eq!(f.recv(), Ok(vec![0, 1, 2, 3, 4, 5]));
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn limit_parallelism_err() {
+        let tasks: Vec<Box<dyn FnOnce() -> Future<u32, u32> + Send>> = vec![
+            Box::new(|| Future::unit(1)),
+            Box::new(|| Future::err(2)),
+            Box::new(|| Future::unit(3))
+        ];
+
+        assert_eq!(Future::limit_parallelism(tasks, 1).recv(), Err(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "error trying to wait for channel.")]
+    fn limit_parallelism_dropped_sender() {
+        let tasks: Vec<Box<dyn FnOnce() -> Future<u32> + Send>> = vec![
+            Box::new(|| Promise::new().future()),
+            Box::new(|| Future::unit(2))
+        ];
+
+        Future::limit_parallelism(tasks, 1).recv().unwrap();
+    }
+
+    #[test]
+    fn limit_parallelism_never() {
+        let tasks: Vec<Box<dyn FnOnce() -> Future<u32> + Send>> = vec![
+            Box::new(Future::never),
+            Box::new(|| Future::unit(2))
+        ];
+
+        let f = Future::limit_parallelism(tasks, 2);
+        thread::sleep(Duration::from_millis(20));
+
+        assert!(f.try_await().is_err());
+    }
+
+    #[test]
+    fn pending_combinators_leave_the_pool_free() {
+        let (senders, pending): (Vec<_>, Vec<_>) = (0..::num_cpus::get() as u32).map(|n| {
+            let (tx, rx) = channel();
+            (tx, Future::<u32>::from_async_channel(rx).map(move |v| v + n))
+        }).unzip();
+
+        // Would never run if each pending combinator held on to a pool thread.
+        let other: Future<u32> = Future::new(|| Async::Ok(7));
+        assert_eq!(other.recv(), Ok(7));
+
+        for tx in senders {
+            tx.send(Async::Ok(1)).unwrap();
+        }
+
+        for (n, f) in pending.into_iter().enumerate() {
+            assert_eq!(f.recv(), Ok(1 + n as u32));
+        }
+    }
+
+    #[test]
+    fn set_global_pool_replaces() {
+        let pool = Arc::new(FuturePool::new(1));
+
+        set_global_pool(pool.clone());
+
+        assert!(Arc::ptr_eq(&global_pool(), &pool));
+        assert_eq!(Future::<u32>::new(|| Async::Ok(1)).recv(), Ok(1));
+    }
+
+    #[test]
+    fn progress_reports_until_done() {
+        let reports = Arc::new(AtomicUsize::new(0));
+        let counter = reports.clone();
+
+        let f: Future<u32> = Future::with_stack_size(64 * 1024, || {
+            thread::sleep(Duration::from_millis(50));
+            Async::Ok(1)
+        });
+        let f = f.progress(move || { counter.fetch_add(1, Ordering::SeqCst); }, Duration::from_millis(5));
+
+        assert_eq!(f.recv(), Ok(1));
+
+        let seen = reports.load(Ordering::SeqCst);
+        assert!(seen > 0);
+
+        thread::sleep(Duration::from_millis(30));
+        assert!(reports.load(Ordering::SeqCst) <= seen + 1);
+    }
+
+    #[test]
+    fn progress_stops_on_dropped_promise() {
+        let reports = Arc::new(AtomicUsize::new(0));
+        let counter = reports.clone();
+
+        let mut promise = Promise::<u32>::new();
+        let _f = promise.future().progress(move || { counter.fetch_add(1, Ordering::SeqCst); }, Duration::from_millis(5));
+
+        thread::sleep(Duration::from_millis(20));
+        drop(promise);
+        thread::sleep(Duration::from_millis(20));
+
+        let seen = reports.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(reports.load(Ordering::SeqCst), seen);
+    }
+
+    #[test]
+    fn async_map_future_resolved() {
+        let val: Async<u32, ()> = Async::Ok(1);
+        let val = val.map_future(|f| f.map(|n| n + 1));
+
+        assert!(val.is_future());
+        assert_eq!(val.to_future().recv(), Ok(2));
+    }
+
+    #[test]
+    fn await_timeout_or_default_pending() {
+        let mut p = Promise::<u32>::new();
+        let f = p.future();
+
+        assert_eq!(f.await_timeout_or_default(Duration::from_millis(10), 7), 7);
+
+        let f: Future<u32> = Future::new(|| Continue(Future::unit(3)));
+        assert_eq!(f.await_timeout_or_default(Duration::from_secs(1), 7), 3);
+    }
+
+    #[test]
+    fn interpose_each_link() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        let f: Future<u32> = Future::new(|| {
+            Continue(Future::new(|| Continue(Future::new(|| Async::Ok(3)))))
+        });
+        let f = f.interpose(move || { counter.fetch_add(1, Ordering::SeqCst); });
+
+        assert_eq!(f.recv(), Ok(3));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn cancel_on_drop_kept() {
+        let mut p = Promise::<u32, Cancelled>::new();
+        let (f, guard) = p.future().with_cancel_on_drop();
+
+        p.resolve(1);
+
+        assert_eq!(f.recv(), Ok(1));
+        drop(guard);
+    }
+
+    #[test]
+    fn cancel_on_drop_pending() {
+        let mut p = Promise::<u32, Cancelled>::new();
+        let (f, guard) = p.future().with_cancel_on_drop();
+
+        drop(guard);
+
+        assert_eq!(f.recv(), Err(Cancelled));
+        p.resolve(1);
+    }
+
+    #[test]
+    fn async_ap_combinations() {
+        type F = fn(u32) -> u32;
+
+        fn ok() -> Async<F, u32> { Async::Ok(|x| x * 2) }
+        fn err() -> Async<F, u32> { Async::Err(1) }
+        fn cont() -> Async<F, u32> { Continue(Future::unit(|x| x * 2)) }
+
+        let resolve = |val: Async<u32, u32>| val.to_future().recv();
+
+        assert_eq!(resolve(Async::ap(ok(), Async::Ok(5))), Ok(10));
+        assert_eq!(resolve(Async::ap(ok(), Async::Err(2))), Err(2));
+        assert_eq!(resolve(Async::ap(ok(), Continue(Future::unit(5)))), Ok(10));
+
+        assert_eq!(resolve(Async::ap(err(), Async::Ok(5))), Err(1));
+        assert_eq!(resolve(Async::ap(err(), Async::Err(2))), Err(1));
+        assert_eq!(resolve(Async::ap(err(), Continue(Future::unit(5)))), Err(1));
+
+        assert_eq!(resolve(Async::ap(cont(), Async::Ok(5))), Ok(10));
+        assert_eq!(resolve(Async::ap(cont(), Async::Err(2))), Err(2));
+        assert_eq!(resolve(Async::ap(cont(), Continue(Future::unit(5)))), Ok(10));
+    }
+
+    #[test]
+    fn async_ap_err_order() {
+        let ff: Async<fn(u32) -> u32, u32> = Continue(Future::err(1));
+
+        assert_eq!(Async::ap(ff, Async::Err(2)).to_future().recv(), Err(1));
+    }
+
+    #[test]
+    fn par_flat_map_err() {
+        let f = Future::<u32, u32>::par_flat_map(vec![1, 2, 3], |n| {
+            vec![if n == 2 { Future::err(n) } else { Future::unit(n) }]
+        });
+
+        assert_eq!(f.recv(), Err(2));
+    }
+
+    #[test]
+    fn par_flat_map_bounded_order() {
+        let f = Future::<u32>::par_flat_map_bounded(vec![3, 0, 2], 2, |n| {
+            (0..n).map(|i| Future::new(move || Async::Ok(n * 10 + i))).collect()
+        });
+
+        assert_eq!(f.recv(), Ok(vec![30, 31, 32, 20, 21]));
+    }
+
+    #[test]
+    fn zip_errors() {
+        let a: Future<u32, u32> = Future::err(1);
+        let b: Future<u32, u32> = Future::err(2);
+        assert_eq!(a.zip(b).recv(), Err(1));
+
+        let a: Future<u32, u32> = Future::unit(1);
+        let b: Future<u32, u32> = Future::err(2);
+        assert_eq!(a.zip(b).recv(), Err(2));
+    }
+
+    #[test]
+    fn select_first_err() {
+        let mut p = Promise::<u32, u32>::new();
+        let pending = p.future();
+        let failed: Future<u32, u32> = Future::err(2);
+
+        assert_eq!(pending.select(failed).recv(), Err(2));
+    }
+
+    #[test]
+    fn select_dropped_loser() {
+        let mut dropped = Promise::<u32>::new();
+        let gone = dropped.future();
+        drop(dropped);
+
+        let ready: Future<u32> = Future::new(|| {
+            thread::sleep(Duration::from_millis(10));
+            Async::Ok(1)
+        });
+
+        assert_eq!(gone.select(ready).recv(), Ok(Either::Right(1)));
+    }
+
+    #[test]
+    fn join_all_completion_order() {
+        let mut first = Promise::<u32, u32>::new();
+        let mut second = Promise::<u32, u32>::new();
+        let all = Future::join_all(vec![first.future(), second.future()]);
+
+        second.resolve(2);
+        first.resolve(1);
+
+        assert_eq!(all.recv(), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn join_all_many_pending() {
+        let mut promises = (0..2000).map(|_| Promise::<u32>::new()).collect::<Vec<_>>();
+        let all = Future::join_all(promises.iter_mut().map(|p| p.future()).collect());
+
+        for (n, p) in promises.iter_mut().enumerate() {
+            p.resolve(n as u32);
+        }
+
+        assert_eq!(all.recv().map(|values| values.len()), Ok(2000));
+    }
+
+    #[test]
+    fn block_on_first_skips_dropped_promises() {
+        let mut dropped = Promise::<u32>::new();
+        let gone = dropped.future();
+        drop(dropped);
+
+        let ready: Future<u32> = Future::new(|| {
+            thread::sleep(Duration::from_millis(10));
+            Async::Ok(1)
+        });
+
+        assert_eq!(block_on_first(vec![gone, ready]), Async::Ok(1));
+    }
+
+    #[test]
+    fn deep_continue_chain() {
+        fn countdown(n: u32) -> Future<u32> {
+            Future::new(move || if n == 0 { Async::Ok(0) } else { Continue(countdown(n - 1)) })
+        }
+
+        assert_eq!(countdown(50_000).wait(), Async::Ok(0));
+        assert_eq!(countdown(50_000).recv(), Ok(0));
+    }
+
+    #[test]
+    fn recursion_limit_exact() {
+        let f: Future<u32, RecursionLimitExceeded> = Future::new(|| Continue(Future::new(|| Async::Ok(1))));
+        assert_eq!(f.with_recursion_limit(1).recv(), Ok(1));
+
+        let f: Future<u32, RecursionLimitExceeded> = Future::new(|| Continue(Future::new(|| Async::Ok(1))));
+        assert_eq!(f.with_recursion_limit(0).recv(), Err(RecursionLimitExceeded));
+    }
+
+    #[test]
+    fn timeout_passes_through() {
+        let f: Future<u32, &str> = Future::unit(1);
+
+        assert_eq!(f.timeout(Duration::from_secs(5), "timed out").recv(), Ok(1));
+    }
+
+    #[test]
+    fn recover_skipped_on_ok() {
+        let f: Future<u32, u32> = Future::unit(1);
+        assert_eq!(f.recover(|_| panic!("recovered an Ok")).recv(), Ok(1));
+
+        let f: Future<u32, u32> = Future::unit(1);
+        assert_eq!(f.recover_with(|_| panic!("recovered an Ok")).recv(), Ok(1));
+    }
+
+    #[test]
+    fn recover_with_fails_again() {
+        let f: Future<u32, u32> = Future::err(1);
+
+        assert_eq!(f.recover_with(|e| Future::err(e + 1)).recv(), Err(2));
+    }
+
+    #[test]
+    fn coerce_err_from() {
+        let f: Future<u32, u8> = Future::err(7);
+        let f: Future<u32, u64> = f.coerce_err();
+        assert_eq!(f.recv(), Err(7u64));
+
+        let val: Async<u32, u8> = Async::Err(7);
+        match val.coerce_err::<u64>() {
+            Async::Err(e) => assert_eq!(e, 7),
+            _ => panic!("unexpected")
+        }
+    }
+
+    #[test]
+    fn future_map_err_ok_untouched() {
+        let f: Future<u32, u32> = Future::unit(1);
+
+        assert_eq!(f.map_err(|_| panic!("mapped an Ok")).recv(), Ok::<u32, ()>(1));
+    }
+
+    #[test]
+    fn zip_and_then_concurrent() {
+        let (tx_a, rx_a) = channel();
+        let (tx_b, rx_b) = channel();
+
+        // Each side only succeeds if it hears from the other while still running.
+        let a: Future<u32, &str> = Future::with_stack_size(64 * 1024, move || {
+            tx_a.send(()).unwrap();
+            Async::lift_result(rx_b.recv_timeout(Duration::from_secs(5)).map(|_| 1).map_err(|_| "a timed out"))
+        });
+        let b: Future<u32, &str> = Future::with_stack_size(64 * 1024, move || {
+            tx_b.send(()).unwrap();
+            Async::lift_result(rx_a.recv_timeout(Duration::from_secs(5)).map(|_| 2).map_err(|_| "b timed out"))
+        });
+
+        assert_eq!(a.zip_and_then(b, |a, b| Async::Ok(a + b)).recv(), Ok(3));
+    }
+
+    #[test]
+    fn zip_and_then_errors() {
+        let a: Future<u32, u32> = Future::unit(1);
+        let b: Future<u32, u32> = Future::err(2);
+        assert_eq!(a.zip_and_then(b, |a, b| Async::Ok(a + b)).recv(), Err(2));
+
+        let a: Future<u32, u32> = Future::err(1);
+        let b: Future<u32, u32> = Future::unit(2);
+        assert_eq!(a.zip_and_then(b, |a, b| Async::Ok(a + b)).recv(), Err(1));
+    }
+
+    #[test]
+    fn compose_macro() {
+        let f: Future<u32> = Future::new(|| compose!(Future::new(|| Async::Ok(42))));
+        assert_eq!(f.recv(), Ok(42));
+
+        let f: Future<u32> = Future::unit(1).and_then(|n| compose! {
+            let next = n + 1;
+            Future::new(move || Async::Ok(next))
+        });
+        assert_eq!(f.recv(), Ok(2));
+    }
+
+    #[test]
+    fn async_short_circuit_ok() {
+        let all: Vec<Async<u32, ()>> = vec![Async::Ok(1), Async::Ok(2)];
+
+        assert_eq!(Async::short_circuit(all).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn async_short_circuit_continue() {
+        let vals: Vec<Async<u32, ()>> = vec![Async::Ok(1), Continue(Future::unit(2))];
+
+        Async::short_circuit(vals);
+    }
+
+    #[test]
+    fn on_resolve_skips_errors() {
+        let (tx, rx) = channel();
+        let f: Future<u32, u32> = Future::err(1);
+
+        assert_eq!(f.on_resolve(tx).recv(), Err(1));
+        assert!(rx.recv().is_err());
+
+        let mut p = Promise::<u32>::new();
+        let copy = p.future();
+        let f: Future<u32, u32> = Future::unit(2);
+
+        assert_eq!(f.on_resolve_async(p).recv(), Ok(2));
+        assert_eq!(copy.recv(), Ok(2));
+    }
+
+    #[test]
+    fn on_resolve_async_settled_promise() {
+        let mut p = Promise::<u32>::new();
+        let copy = p.future();
+        p.resolve(1);
+
+        let f: Future<u32, u32> = Future::unit(2);
+
+        assert_eq!(f.on_resolve_async(p).recv(), Ok(2));
+        assert_eq!(copy.recv(), Ok(1));
+    }
+
+    #[test]
+    fn promise() {
+        let mut m = Promise::<u32>::new();
+        let f = m.future();
+
+        // Do some calculation...
+        m.resolve(123);
+
+        assert_eq!(f.recv(), Ok(123));
+    }
+
+    #[test]
+    fn promise_settles_once() {
+        let mut m = Promise::<u32, u32>::new();
+        let f = m.future();
+
+        assert_eq!(m.try_reject(1), Ok(()));
+        assert_eq!(m.try_resolve(2), Err(AlreadyResolvedError));
+        assert_eq!(m.try_reject(3), Err(AlreadyResolvedError));
+        assert_eq!(f.recv(), Err(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot resolve")]
+    fn promise_resolve_twice() {
+        let mut m = Promise::<u32>::new();
+
+        m.resolve(1);
+        m.resolve(2);
+    }
+
+    #[test]
+    fn shared_future_many_consumers() {
+        let mut p = Promise::<u32, u32>::new();
+        let shared: SharedFuture<u32, u32> = SharedFuture::from(p.future());
+
+        let handles = (0..4).map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || shared.wait().unwrap())
+        }).collect::<Vec<_>>();
+
+        assert!(!shared.is_ready());
+        p.resolve(9);
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 9);
         }
+
+        assert!(shared.is_ready());
+        assert_eq!(shared.to_future().recv(), Ok(9));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::thread;
-    use std::time::Duration;
-    use std::sync::mpsc::{channel, Receiver, Sender};
+    #[test]
+    fn async_result_conversions() {
+        use std::convert::TryInto;
+
+        let val: Async<u32, u32> = Ok(1).into();
+        assert_eq!(val.into_result(), Ok(1));
+
+        let val: Async<u32, u32> = Async::from_result(Err(2));
+        let res: Result<Result<u32, u32>, _> = val.try_into();
+        assert_eq!(res.unwrap(), Err(2));
+
+        let val: Async<u32, u32> = Continue(Future::unit(3));
+        let res: Result<Result<u32, u32>, _> = val.try_into();
+        assert_eq!(res.unwrap_err().future.recv(), Ok(3));
+    }
 
     #[test]
-    fn async_macro() {
-        fn foo() -> Async<u32, ()> {
-            let v = Ok(123);
-            let v = async!(v);
-            assert_eq!(v, 123);
-            Async::Ok(v)
-        }
+    #[should_panic(expected = "not resolved yet")]
+    fn async_unwrap_continue() {
+        let val: Async<u32, ()> = Continue(Future::unit(1));
 
-        foo();
+        val.unwrap();
     }
 
     #[test]
-    fn async_macro_err() {
-        fn foo() -> Async<u32, u32> {
-            let v = Err(123u32);
-            let v = async!(v);
-            Async::Ok(0)
-        }
+    #[should_panic(expected = "loading config: \"missing\"")]
+    fn async_expect_err() {
+        let val: Async<u32, &str> = Async::Err("missing");
 
-        assert!(foo().is_err());
+        val.expect("loading config");
     }
 
     #[test]
-    fn async_map() {
-        let val: Async<u32, ()> = Async::Ok(123);
-        match val.map(|x| x * 2) {
-            Async::Ok(v) => assert_eq!(v, 246),
-            _ => panic!("Unexpected error.")
+    fn then_sees_resolved() {
+        let f: Future<u32, u32> = Future::new(|| Continue(Future::err(3)));
+        let f: Future<bool, ()> = f.then(|val| Async::Ok(val.is_err()));
+
+        assert_eq!(f.recv(), Ok(true));
+    }
+
+    #[test]
+    fn filter_map_none() {
+        let f: Future<u32, String> = Future::unit(3);
+        let f = f.filter_map_or_else(|n| if n % 2 == 0 { Some(n) } else { None }, || "odd".to_string());
+
+        assert_eq!(f.recv(), Err("odd".to_string()));
+    }
+
+    #[test]
+    fn from_channel_with_err_value() {
+        let (tx, rx) = channel();
+        let f: Future<u32, &str> = Future::from_channel_with_err(rx, "sender dropped");
+
+        tx.send(4).unwrap();
+
+        assert_eq!(f.map(|n| n + 1).recv(), Ok(5));
+    }
+
+    #[test]
+    fn retry_exhausted() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        let f: Future<u32, usize> = Future::retry_with_backoff(move || {
+            Future::err(counter.fetch_add(1, Ordering::SeqCst))
+        }, 3, Duration::from_millis(1));
+
+        assert_eq!(f.recv(), Err(2));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    fn block_on<F: StdFuture>(f: F) -> F::Output {
+        use std::task::Wake;
+
+        struct Unparker(thread::Thread);
+
+        impl Wake for Unparker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unparker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut f = Box::pin(f);
+
+        loop {
+            if let Poll::Ready(val) = f.as_mut().poll(&mut cx) {
+                return val;
+            }
+
+            thread::park();
         }
     }
 
     #[test]
-    #[should_panic]
-    fn async_map_fail() {
-        let val: Async<u32, ()> = Async::Continue(future! {
-            Async::Ok(123)
+    fn std_future_ready() {
+        let f: Future<u32> = Future::unit(5);
+
+        assert_eq!(block_on(f).unwrap(), 5);
+    }
+
+    #[test]
+    fn std_future_pending() {
+        let mut promise = Promise::<u32, ()>::new();
+        let f = promise.future().map(|n| n + 1);
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            promise.resolve(1);
         });
 
-        val.map(|x| x * 2);
+        assert_eq!(block_on(f).unwrap(), 2);
     }
 
     #[test]
-    fn async_map_err() {
-        let val: Async<(), u32> = Async::Err(1);
-        match val.map_err(|x| x + 5) {
-            Async::Err(e) => assert_eq!(e, 6),
-            _ => panic!("Unexpected error")
+    fn std_future_follows_continue() {
+        let f: Future<u32> = Future::unit(1).and_then(|n| Continue(Future::new(move || {
+            thread::sleep(Duration::from_millis(10));
+            Async::Ok(n * 3)
+        })));
+
+        assert_eq!(block_on(f).unwrap(), 3);
+    }
+
+    #[test]
+    fn many_links_on_pending_future() {
+        let mut promise = Promise::<u32, ()>::new();
+        let mut f = promise.future();
+
+        // Every link waits on the one before it, none of them gets a thread.
+        for _ in 0..10_000 {
+            f = f.map(|n| n + 1);
         }
+
+        promise.resolve(0);
+
+        assert_eq!(f.recv(), Ok(10_000));
     }
 
     #[test]
-    fn create_channel() {
-        let (tx, future) = Future::<u32>::channel();
+    fn try_await_pending() {
+        let mut promise = Promise::<u32, ()>::new();
+        let f = promise.future();
 
-        tx.send(123);
+        let f = match f.try_await() {
+            Ok(_) => panic!("Unexpected value"),
+            Err(f) => f
+        };
 
-        match future.recv() {
-            Ok(v) => assert_eq!(v, 123),
-            Err(err) => panic!("Unexpected case.")
+        promise.resolve(3);
+
+        assert_eq!(f.await_or_panic(), 3);
+    }
+
+    #[test]
+    fn try_await_ready() {
+        let f: Future<u32, u32> = Future::err(4);
+
+        assert_eq!(f.try_await().unwrap().unwrap_err(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "error trying to wait for channel.")]
+    fn try_await_dropped_promise() {
+        let mut promise = Promise::<u32, ()>::new();
+        let f = promise.future();
+        drop(promise);
+
+        let _ = f.try_await();
+    }
+
+    #[test]
+    fn cancellation_passes_through() {
+        let (_token, handle) = CancellationToken::new();
+        let f: Future<u32, Cancelled> = Future::unit(1).with_cancellation(handle);
+
+        assert_eq!(f.recv(), Ok(1));
+    }
+
+    #[test]
+    fn cancellation_shared_scope() {
+        let (token, handle) = CancellationToken::new();
+        let mut a = Promise::<u32, Cancelled>::new();
+        let mut b = Promise::<u32, Cancelled>::new();
+
+        let fa = a.future().with_cancellation(handle.clone());
+        let fb = b.future().with_cancellation(token.handle());
+
+        token.clone().cancel();
+
+        assert_eq!(fa.recv(), Err(Cancelled));
+        assert_eq!(fb.recv(), Err(Cancelled));
+        assert!(handle.is_cancelled());
+    }
+
+    #[test]
+    fn cancellation_after_cancel() {
+        let (token, handle) = CancellationToken::new();
+        token.cancel();
+
+        let mut p = Promise::<u32, Cancelled>::new();
+
+        assert_eq!(p.future().with_cancellation(handle).recv(), Err(Cancelled));
+    }
+
+    #[test]
+    fn cancellation_unregisters_resolved() {
+        let (_token, handle) = CancellationToken::new();
+
+        for n in 0..10 {
+            let f: Future<u32, Cancelled> = Future::unit(n).with_cancellation(handle.clone());
+            assert_eq!(f.recv(), Ok(n));
         }
+
+        assert!(handle.state.lock().unwrap().callbacks.is_empty());
     }
 
     #[test]
-    fn from_chan1() {
+    fn on_success_and_failure() {
         let (tx, rx) = channel();
-        let f: Future<u32> = Future::from_channel(rx);
+        let err_tx = tx.clone();
 
-        let f = f.map(|n| {
-            assert_eq!(n, 555);
-            n + 5
-        }).map(|n| {
-            assert_eq!(n, 560);
-            n
-        });
+        let ok: Future<u32, u32> = Future::unit(1);
+        let ok = ok.on_success(move |n| tx.send(*n).unwrap()).on_failure(|_| panic!("Unexpected error"));
+        assert_eq!(ok.recv(), Ok(1));
 
-        // Resolve the future through the sender half of the channel.
-        tx.send(555);
+        let failed: Future<u32, u32> = Future::err(2);
+        let failed = failed.on_success(|_| panic!("Unexpected value")).on_failure(move |e| err_tx.send(*e).unwrap());
+        assert_eq!(failed.recv(), Err(2));
 
-        assert_eq!(f.recv().unwrap(), 560);
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![1, 2]);
     }
 
     #[test]
-    fn to_future_macro() {
-        future! {
-            if true {
-                Async::Ok(123)
-            } else {
-                Async::Err(5)
-            }
-        };
+    fn broadcast_promise() {
+        let mut p = BroadcastPromise::<u32, &str>::new();
+        let early = p.future().map(|n| n + 1);
+        drop(p.future());
+
+        p.reject("gone");
+
+        assert_eq!(p.state(), PromiseState::Failed);
+        assert_eq!(early.recv(), Err("gone"));
+        assert_eq!(p.future().recv(), Err("gone"));
+        assert_eq!(p.try_resolve(1), Err(AlreadyResolvedError));
     }
 
     #[test]
-    fn test_async() {
-        let f: Future<usize> = future! { Async::Ok(5) };
+    fn inspect_sees_outcome() {
+        let (tx, rx) = channel();
+        let f: Future<u32> = Future::new(|| Async::Ok(1)).and_then(|n| Continue(Future::unit(n + 1)));
 
-        let next = f.and_then(|n| {
-            thread::sleep(Duration::from_millis(50));
+        let f = f.inspect(move |val| tx.send(val.is_ok()).unwrap());
 
-            Continue(Future::new(move || {
-                thread::sleep(Duration::from_millis(100));
-                Async::Ok(n * 100)
-            }))
+        assert_eq!(f.recv(), Ok(2));
+        assert_eq!(rx.recv(), Ok(true));
+    }
+
+    #[test]
+    fn async_flatten() {
+        let val: Async<Async<u32, ()>, ()> = Async::Ok(Async::Ok(1));
+        assert_eq!(val.flatten().unwrap(), 1);
+
+        let val: Async<Async<u32, u32>, u32> = Async::Err(2);
+        assert_eq!(val.flatten().unwrap_err(), 2);
+
+        let val: Async<Async<u32, ()>, ()> = Async::Ok(Continue(Future::unit(3)));
+        assert!(val.flatten().is_future());
+
+        let val: Async<Async<u32, ()>, ()> = Continue(Future::unit(Async::Ok(4)));
+        match val.flatten() {
+            Continue(f) => assert_eq!(f.recv(), Ok(4)),
+            _ => panic!("Unexpected value")
+        }
+    }
+
+    #[test]
+    fn future_flatten() {
+        let f: Future<Future<u32, &str>, &str> = Future::new(|| Async::Ok(Future::new(|| Async::Err("inner"))));
+        assert_eq!(f.flatten().recv(), Err("inner"));
+
+        let f: Future<Future<u32, &str>, &str> = Future::err("outer");
+        assert_eq!(f.flatten().recv(), Err("outer"));
+    }
+
+    #[test]
+    fn async_clone_eq() {
+        let val: Async<u32, &str> = Async::Err("failed");
+
+        assert_eq!(val.clone(), val);
+        assert!(val != Async::Ok(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot clone")]
+    fn async_clone_continue() {
+        let val: Async<u32, ()> = Continue(Future::unit(1));
+        let _ = val.clone();
+    }
+
+    #[test]
+    fn async_error_source() {
+        use std::error::Error;
+        use std::io;
+
+        let err = AsyncError(io::Error::other("refused"));
+
+        assert_eq!(err.to_string(), "future failed: refused");
+        assert_eq!(err.source().unwrap().to_string(), "refused");
+
+        let val: Async<u32, &str> = Async::Ok(1);
+        assert_eq!(val.to_string(), "Async::Ok(1)");
+    }
+
+    #[test]
+    fn delay_doesnt_block() {
+        let start = Instant::now();
+        let f: Future<(), u32> = Future::delay(Duration::from_millis(50));
+
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(f.recv(), Ok(()));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn and_then_err_paths() {
+        let ok: Future<u32, u32> = Future::unit(1);
+        assert_eq!(ok.and_then_err(|_| Async::Err::<u32, &str>("unreachable")).recv(), Ok(1));
+
+        let failed: Future<u32, u32> = Future::err(2);
+        let f = failed.and_then_err(|n| Continue(Future::<u32, ()>::new(move || Async::Ok(n * 10))));
+        assert_eq!(f.recv(), Ok(20));
+    }
+
+    #[test]
+    fn any_errors_in_input_order() {
+        let slow: Future<u32, &str> = Future::with_stack_size(64 * 1024, || {
+            thread::sleep(Duration::from_millis(20));
+            Async::Err("slow")
         });
+        let fast: Future<u32, &str> = Future::err("fast");
 
-        match next.recv() {
-            Ok(n) => assert_eq!(n, 500),
-            Err(err) => panic!("Unexpected value")
+        assert_eq!(Future::any(vec![slow, fast]).recv(), Err(vec!["slow", "fast"]));
+    }
+
+    #[test]
+    fn fold_sequential() {
+        let f = Future::<u32, u32>::fold(0..1000, 0, |sum, n| {
+            if n % 100 == 0 { Continue(Future::new(move || Async::Ok(sum + n))) } else { Async::Ok(sum + n) }
+        });
+
+        assert_eq!(f.recv(), Ok(499_500));
+    }
+
+    #[test]
+    fn fold_stops_at_err() {
+        let f = Future::<u32, u32>::fold(vec![1, 2, 3], 0, |sum, n| {
+            assert!(n < 3, "fold kept going after an error");
+            if n == 2 { Async::Err(sum) } else { Async::Ok(sum + n) }
+        });
+
+        assert_eq!(f.recv(), Err(1));
+    }
+
+    #[test]
+    fn traverse_starts_all() {
+        let (tx, rx) = channel();
+
+        let f = Future::<u32>::traverse(0..3, move |n| {
+            tx.send(n).unwrap();
+            Future::unit(n + 1)
+        });
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(f.recv(), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn executor_carries_through_timeout() {
+        struct Counting(AtomicUsize);
+
+        impl Executor for Counting {
+            fn execute(&self, task: Box<dyn FnOnce() + Send>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                global_pool().execute(task);
+            }
         }
+
+        let counting = Arc::new(Counting(AtomicUsize::new(0)));
+        let executor = FutureExecutor::new(counting.clone());
+
+        let f: Future<u32, &str> = Future::new_on(&executor, || Async::Ok(1));
+        let f = f.timeout(Duration::from_secs(5), "timed out").map(|n| n + 1);
+
+        assert_eq!(f.recv(), Ok(2));
+        // The closure and the `map` after the timeout.
+        assert!(counting.0.load(Ordering::SeqCst) >= 2);
     }
 
     #[test]
-    fn resolve_from_value() {
-        let val: Future<usize> = Future::unit(5);
+    fn dropped_future_still_runs() {
+        struct Tracked(Sender<&'static str>);
 
-        match val.recv() {
-            Ok(n) => assert_eq!(n, 5),
-            Err(err) => panic!("Unexpected value")
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                let _ = self.0.send("value dropped");
+            }
         }
+
+        let executor = FutureExecutor::new(Arc::new(FuturePool::new(1)));
+        let (block_tx, block_rx) = channel::<()>();
+        let (ran_tx, ran_rx) = channel();
+
+        executor.execute(move || { let _ = block_rx.recv(); });
+
+        let dropped: Future<Tracked> = Future::new_on(&executor, move || {
+            ran_tx.send("ran").unwrap();
+            Async::Ok(Tracked(ran_tx))
+        });
+
+        drop(dropped);
+        block_tx.send(()).unwrap();
+
+        // The work still runs, its value is dropped along with the channel.
+        assert_eq!(ran_rx.recv(), Ok("ran"));
+        assert_eq!(ran_rx.recv(), Ok("value dropped"));
+        assert!(ran_rx.recv().is_err());
     }
 
     #[test]
-    fn map_promise() {
-        let count: Future<usize> = Future::unit(5);
+    fn zip_with_err_skips_f() {
+        let a: Future<u32, &str> = Future::unit(1);
+        let b: Future<u32, &str> = Future::new(|| Async::Err("b failed"));
 
-        let curr: Future<usize> = count.and_then(|n| {
-            Continue(future! {
-                Async::Ok(100)
-            })
+        assert_eq!(a.zip_with(b, |_, _| -> u32 { panic!("Unexpected call") }).recv(), Err("b failed"));
+    }
+
+    #[test]
+    fn join_macro() {
+        let start = Instant::now();
+        let slow = |n: u32| Future::<u32, &str>::with_stack_size(64 * 1024, move || {
+            thread::sleep(Duration::from_millis(30));
+            Async::Ok(n)
         });
 
-        match curr.recv() {
-            Ok(n) => assert_eq!(n, 100),
-            Err(err) => panic!("Unexpected value")
+        let name: Future<&str, &str> = Future::unit("x");
+        assert_eq!(join!(slow(1), slow(2), name, slow(3)), Async::Ok((1, 2, "x", 3)));
+        assert!(start.elapsed() < Duration::from_millis(90));
+
+        let failed: Future<u32, &str> = Future::err("failed");
+        assert_eq!(join!(slow(1), failed), Async::Err("failed"));
+    }
+
+    #[test]
+    fn race_macro() {
+        let mut a = Promise::<u32, &str>::new();
+        let mut b = Promise::<&str, &str>::new();
+        let c: Future<(), &str> = Future::new(|| Async::Err("c failed"));
+
+        assert_eq!(race!(a.future(), b.future(), c), Async::Err("c failed"));
+
+        let mut a = Promise::<u32, &str>::new();
+        let mut b = Promise::<&str, &str>::new();
+        let mut c = Promise::<(), &str>::new();
+        let d: Future<u8, &str> = Future::unit(4);
+
+        assert_eq!(race!(a.future(), b.future(), c.future(), d), Async::Ok(RaceResult::Fourth(4)));
+    }
+
+    #[test]
+    fn try_future_macro() {
+        fn parse(s: &'static str) -> Future<u32, String> {
+            try_future! {
+                let n: u32 = s.parse().map_err(|_| format!("bad input {:?}", s))?;
+                Async::Ok(n * 2)
+            }
         }
+
+        assert_eq!(parse("21").recv(), Ok(42));
+        assert_eq!(parse("x").recv(), Err("bad input \"x\"".to_string()));
+    }
+
+    #[test]
+    fn never_with_timeout() {
+        let f: Future<u32, &str> = Future::never();
+        let f = f.and_then(|n| Async::Ok(n + 1)).timeout(Duration::from_millis(10), "timed out");
+
+        assert_eq!(f.recv(), Err("timed out"));
+    }
+
+    #[test]
+    fn never_loses_select() {
+        let f: Future<u32> = Future::never();
+
+        assert_eq!(f.select(Future::<u32>::unit(1)).recv(), Ok(Either::Right(1)));
+    }
+
+    #[test]
+    fn never_combinators_stay_never() {
+        let f: Future<u32, &str> = Future::never();
+        let f = f.map(|n| n + 1).and_then(|n| Async::Ok(n * 2)).map_err(|err| err);
+
+        assert!(f.is_never());
+        assert!(Future::join_all(vec![f, Future::never()]).is_never());
     }
 
-    // #[test]
-    // fn promise() {
-    //     let mut m = Promise::new();
+    #[test]
+    fn never_mixed_with_ready() {
+        let futures = vec![
+            Future::join_all(vec![Future::<u32, &str>::never(), Future::unit(1)]).map(|_| ()),
+            Future::await_n(vec![Future::never(), Future::unit(1), Future::err("down")], 2).map(|_| ()),
+            Future::for_each_parallel(vec![0, 1], |n| if n == 0 { Future::never() } else { Future::unit(()) })
+        ];
 
-    //     // Do some calculation...
-    //     m.success(123);
+        // Past the inputs that resolve, nothing is left to complete any of them.
+        thread::sleep(Duration::from_millis(20));
 
-    //     m.future().recv()
-    // }
+        for f in futures {
+            assert!(f.try_await().is_err());
+        }
+    }
 }
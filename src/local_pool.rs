@@ -0,0 +1,154 @@
+//! Single threaded execution of futures on the current thread. Nothing is sent to
+//! another thread, so the values don't have to be `Send`, which also makes this
+//! usable where there are no threads at all, like `wasm32-unknown-unknown`.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+type Queue = Rc<RefCell<VecDeque<Box<dyn FnOnce()>>>>;
+
+/// Queues futures and runs them once `run_until_complete` is called, in the order
+/// they were spawned.
+pub struct LocalPool {
+    queue: Queue
+}
+
+impl LocalPool {
+    /// ```
+    /// use tangle::local_pool::LocalPool;
+    ///
+    /// let pool = LocalPool::new();
+    /// let f = pool.spawn(|| Ok::<u32, ()>(5)).map(|n| n + 1);
+    ///
+    /// pool.run_until_complete();
+    /// assert_eq!(f.try_recv(), Some(Ok(6)));
+    /// ```
+    pub fn new() -> LocalPool {
+        LocalPool { queue: Rc::new(RefCell::new(VecDeque::new())) }
+    }
+
+    pub fn spawn<T, E, F>(&self, f: F) -> LocalFuture<T, E>
+        where F: FnOnce() -> Result<T, E> + 'static,
+              T: 'static,
+              E: 'static
+    {
+        let future = LocalFuture { slot: Rc::new(RefCell::new(None)), queue: self.queue.clone() };
+        let slot = future.slot.clone();
+
+        self.queue.borrow_mut().push_back(Box::new(move || {
+            *slot.borrow_mut() = Some(f());
+        }));
+
+        future
+    }
+
+    /// Run every queued future, including those queued while running, until there
+    /// is nothing left to do.
+    pub fn run_until_complete(&self) {
+        loop {
+            let task = self.queue.borrow_mut().pop_front();
+
+            match task {
+                Some(task) => task(),
+                None => return
+            }
+        }
+    }
+}
+
+impl Default for LocalPool {
+    fn default() -> LocalPool {
+        LocalPool::new()
+    }
+}
+
+/// A value produced by a `LocalPool` once it gets to run.
+pub struct LocalFuture<T, E=()> {
+    slot: Rc<RefCell<Option<Result<T, E>>>>,
+    queue: Queue
+}
+
+impl<T, E> LocalFuture<T, E>
+    where T: 'static,
+          E: 'static
+{
+    /// Queue `f` on the same pool, to run with the value of this future.
+    pub fn and_then<S, F>(self, f: F) -> LocalFuture<S, E>
+        where F: FnOnce(T) -> Result<S, E> + 'static,
+              S: 'static
+    {
+        let future = LocalFuture { slot: Rc::new(RefCell::new(None)), queue: self.queue.clone() };
+        let slot = future.slot.clone();
+        let input = self.slot;
+
+        // Tasks run in the order they were queued, so this one always comes after
+        // whatever resolves `self`.
+        future.queue.borrow_mut().push_back(Box::new(move || {
+            let val = input.borrow_mut().take().expect("local future ran before its input resolved.");
+
+            *slot.borrow_mut() = Some(val.and_then(f));
+        }));
+
+        future
+    }
+
+    pub fn map<S, F>(self, f: F) -> LocalFuture<S, E>
+        where F: FnOnce(T) -> S + 'static,
+              S: 'static
+    {
+        self.and_then(move |val| Ok(f(val)))
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.slot.borrow().is_some()
+    }
+
+    /// Take the result, or `None` if the pool hasn't gotten to this future yet.
+    pub fn try_recv(&self) -> Option<Result<T, E>> {
+        self.slot.borrow_mut().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn runs_on_demand() {
+        let pool = LocalPool::new();
+        let f = pool.spawn(|| Ok::<u32, ()>(1));
+
+        assert!(!f.is_ready());
+
+        pool.run_until_complete();
+
+        assert!(f.is_ready());
+        assert_eq!(f.try_recv(), Some(Ok(1)));
+    }
+
+    #[test]
+    fn non_send_values() {
+        let pool = LocalPool::new();
+        let shared = Rc::new(5);
+        let captured = shared.clone();
+
+        let f = pool.spawn(move || Ok::<Rc<u32>, ()>(captured)).map(|rc| *rc * 2);
+
+        pool.run_until_complete();
+
+        assert_eq!(f.try_recv(), Some(Ok(10)));
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn err_skips_and_then() {
+        let pool = LocalPool::new();
+        let f = pool.spawn(|| Err::<u32, &str>("failed")).and_then(|n| Ok(n + 1));
+
+        pool.run_until_complete();
+
+        assert_eq!(f.try_recv(), Some(Err("failed")));
+    }
+}
@@ -0,0 +1,289 @@
+//! The channel a future's value travels through. It carries a single value, and
+//! unlike a native channel the receiving end can be turned into a callback, which
+//! whoever sends the value then runs. That's how a combinator waits on a future
+//! that hasn't resolved yet without a thread of its own sitting on a `recv`.
+
+use std::fmt;
+use std::sync::mpsc::{self, RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Callback<T> = Box<dyn FnOnce(Option<T>) + Send>;
+
+struct State<T> {
+    value: Option<T>,
+    callback: Option<Callback<T>>,
+    senders: usize,
+    receiver: bool
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    cond: Condvar
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().expect("error acquiring a lock.")
+    }
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State { value: None, callback: None, senders: 1, receiver: true }),
+        cond: Condvar::new()
+    });
+
+    (Sender { shared: shared.clone() }, Receiver { source: Source::Shared(Handle(shared)) })
+}
+
+/// The sending half. Clones share the channel, which disconnects once the last of
+/// them is dropped without anything sent.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>
+}
+
+impl<T> Sender<T> {
+    /// Hand `val` to the receiver, or to the callback it was turned into, which runs
+    /// right here. Fails once the receiver is gone or a value was already sent.
+    pub fn send(&self, val: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+
+        if !state.receiver || state.value.is_some() {
+            return Err(SendError(val));
+        }
+
+        match state.callback.take() {
+            Some(callback) => {
+                state.receiver = false;
+                drop(state);
+                callback(Some(val));
+            },
+            None => {
+                state.value = Some(val);
+                self.shared.cond.notify_all();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.lock().senders += 1;
+
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders > 0 {
+            return;
+        }
+
+        self.shared.cond.notify_all();
+
+        if let Some(callback) = state.callback.take() {
+            state.receiver = false;
+            drop(state);
+            callback(None);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sender").finish()
+    }
+}
+
+/// The receiver's hold on the channel, which tells the senders when it's gone.
+struct Handle<T>(Arc<Shared<T>>);
+
+impl<T> Drop for Handle<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+
+        // A registered callback takes over from the receiver.
+        if state.callback.is_none() {
+            state.receiver = false;
+            let val = state.value.take();
+            drop(state);
+            drop(val);
+        }
+    }
+}
+
+enum Source<T> {
+    Shared(Handle<T>),
+    // Values coming from outside of the crate, through a native channel.
+    Native(mpsc::Receiver<T>)
+}
+
+/// The receiving half. Waiting on it works the same as on a native channel.
+pub struct Receiver<T> {
+    source: Source<T>
+}
+
+impl<T> Receiver<T> {
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let shared = match self.source {
+            Source::Shared(ref handle) => &handle.0,
+            Source::Native(ref rx) => return rx.recv()
+        };
+
+        let mut state = shared.lock();
+
+        loop {
+            if let Some(val) = state.value.take() {
+                return Ok(val);
+            }
+
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+
+            state = shared.cond.wait(state).expect("error acquiring a lock.");
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let shared = match self.source {
+            Source::Shared(ref handle) => &handle.0,
+            Source::Native(ref rx) => return rx.try_recv()
+        };
+
+        let mut state = shared.lock();
+
+        match state.value.take() {
+            Some(val) => Ok(val),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty)
+        }
+    }
+
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, RecvTimeoutError> {
+        let shared = match self.source {
+            Source::Shared(ref handle) => &handle.0,
+            Source::Native(ref rx) => return rx.recv_timeout(dur)
+        };
+
+        let deadline = Instant::now() + dur;
+        let mut state = shared.lock();
+
+        loop {
+            if let Some(val) = state.value.take() {
+                return Ok(val);
+            }
+
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+
+            state = shared.cond.wait_timeout(state, deadline - now).expect("error acquiring a lock.").0;
+        }
+    }
+
+    /// Run `f` with the value once it's sent, or with `None` if the senders went
+    /// away without sending one. When the value is already there `f` runs right
+    /// away, otherwise on the thread that sends it. A native channel has no way
+    /// to call back, so a thread is left waiting on it instead.
+    pub fn on_value<F>(self, f: F)
+        where F: FnOnce(Option<T>) + Send + 'static,
+              T: Send + 'static
+    {
+        let handle = match self.source {
+            Source::Shared(handle) => handle,
+            Source::Native(rx) => {
+                thread::spawn(move || f(rx.recv().ok()));
+                return;
+            }
+        };
+
+        let mut state = handle.0.lock();
+
+        match state.value.take() {
+            Some(val) => {
+                drop(state);
+                f(Some(val));
+            },
+            None if state.senders == 0 => {
+                drop(state);
+                f(None);
+            },
+            None => state.callback = Some(Box::new(f))
+        }
+    }
+}
+
+impl<T> From<mpsc::Receiver<T>> for Receiver<T> {
+    fn from(rx: mpsc::Receiver<T>) -> Receiver<T> {
+        Receiver { source: Source::Native(rx) }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Receiver").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn callback_runs_on_send() {
+        let (tx, rx) = channel::<u32>();
+        let (seen_tx, seen_rx) = mpsc::channel();
+
+        rx.on_value(move |val| seen_tx.send(val).unwrap());
+        assert!(seen_rx.try_recv().is_err());
+
+        tx.send(1).unwrap();
+        assert_eq!(seen_rx.try_recv(), Ok(Some(1)));
+    }
+
+    #[test]
+    fn callback_sees_dropped_sender() {
+        let (tx, rx) = channel::<u32>();
+        let (seen_tx, seen_rx) = mpsc::channel();
+
+        rx.on_value(move |val| seen_tx.send(val).unwrap());
+        drop(tx.clone());
+        assert!(seen_rx.try_recv().is_err());
+
+        drop(tx);
+        assert_eq!(seen_rx.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn send_fails_without_receiver() {
+        let (tx, rx) = channel::<u32>();
+
+        drop(rx);
+        assert!(tx.send(1).is_err());
+    }
+
+    #[test]
+    fn recv_from_another_thread() {
+        let (tx, rx) = channel::<u32>();
+
+        thread::spawn(move || tx.send(2).unwrap());
+
+        assert_eq!(rx.recv(), Ok(2));
+    }
+}
@@ -0,0 +1,185 @@
+//! The thread pool futures are run on. Every future created through `Future::new`
+//! goes to the global pool, see `tangle::global_pool` and `tangle::set_global_pool`,
+//! while `Future::new_on` picks the executor for a whole chain.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use threadpool::ThreadPool;
+
+use {Async, Future};
+use oneshot;
+
+/// A snapshot of how busy a pool is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// Number of threads in the pool.
+    pub threads: usize,
+    /// Tasks currently being run.
+    pub active: usize,
+    /// Tasks waiting for a free thread.
+    pub queued: usize
+}
+
+/// A thread pool that futures can be spawned on.
+#[derive(Debug)]
+pub struct FuturePool {
+    pool: Mutex<ThreadPool>
+}
+
+impl FuturePool {
+    /// ```
+    /// use tangle::{Async, FuturePool};
+    ///
+    /// let pool = FuturePool::new(2);
+    /// let f = pool.spawn(|| Async::Ok::<u32, ()>(5));
+    ///
+    /// assert_eq!(f.recv(), Ok(5));
+    /// ```
+    pub fn new(threads: usize) -> FuturePool {
+        FuturePool { pool: Mutex::new(ThreadPool::new(threads)) }
+    }
+
+    /// Run `task` on one of the pool's threads.
+    pub fn execute<F>(&self, task: F)
+        where F: FnOnce() + Send + 'static
+    {
+        self.pool.lock().expect("error acquiring a lock.").execute(task);
+    }
+
+    /// Same as `Future::new`, except `f` runs on this pool. Only `f` does, whatever
+    /// is chained onto the future runs on the global pool, `Future::new_on` keeps a
+    /// whole chain on one executor.
+    pub fn spawn<T, E, F>(&self, f: F) -> Future<T, E>
+        where F: FnOnce() -> Async<T, E> + Send + 'static,
+              T: Send + 'static,
+              E: Send + 'static
+    {
+        let (tx, rx) = oneshot::channel();
+
+        self.execute(move || { let _ = tx.send(f()); });
+
+        Future::from_oneshot(rx)
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let pool = self.pool.lock().expect("error acquiring a lock.");
+
+        PoolMetrics {
+            threads: pool.max_count(),
+            active: pool.active_count(),
+            queued: pool.queued_count()
+        }
+    }
+}
+
+/// Anything that can run tasks for futures.
+pub trait Executor: Send + Sync {
+    fn execute(&self, task: Box<dyn FnOnce() + Send>);
+}
+
+impl Executor for FuturePool {
+    fn execute(&self, task: Box<dyn FnOnce() + Send>) {
+        FuturePool::execute(self, task);
+    }
+}
+
+/// A shared handle to an `Executor`. Futures created with `Future::new_on` keep it,
+/// so the combinators chained onto them run on the same executor. Combinators that
+/// wait on several futures at once, such as `join_all` or `reduce_err`, don't pick
+/// one of their executors and go back to the global pool.
+#[derive(Clone)]
+pub struct FutureExecutor {
+    executor: Arc<dyn Executor>
+}
+
+impl FutureExecutor {
+    /// ```
+    /// use std::sync::Arc;
+    /// use tangle::{Async, Future, FutureExecutor, FuturePool};
+    ///
+    /// let io = FutureExecutor::new(Arc::new(FuturePool::new(2)));
+    /// let f = Future::<u32>::new_on(&io, || Async::Ok(1)).map(|n| n + 1);
+    ///
+    /// assert_eq!(f.recv(), Ok(2));
+    /// ```
+    pub fn new(executor: Arc<dyn Executor>) -> FutureExecutor {
+        FutureExecutor { executor }
+    }
+
+    /// The global pool, as it is at the time of the call.
+    pub fn global() -> FutureExecutor {
+        FutureExecutor::new(::global_pool())
+    }
+
+    pub fn execute<F>(&self, task: F)
+        where F: FnOnce() + Send + 'static
+    {
+        self.executor.execute(Box::new(task));
+    }
+}
+
+impl fmt::Debug for FutureExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FutureExecutor").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn spawn_on_pool() {
+        let pool = FuturePool::new(1);
+        let f: Future<u32> = pool.spawn(|| Async::Ok(1));
+
+        assert_eq!(f.map(|n| n + 1).recv(), Ok(2));
+    }
+
+    #[test]
+    fn metrics_queued() {
+        let pool = FuturePool::new(1);
+        let (tx, rx) = channel::<()>();
+
+        pool.execute(move || { let _ = rx.recv(); });
+        pool.execute(|| {});
+
+        thread::sleep(Duration::from_millis(20));
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.threads, 1);
+        assert_eq!(metrics.active, 1);
+        assert_eq!(metrics.queued, 1);
+
+        tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn chain_stays_on_executor() {
+        let pool = Arc::new(FuturePool::new(1));
+        let executor = FutureExecutor::new(pool.clone());
+        let (tx, rx) = channel::<()>();
+        let (id_tx, id_rx) = channel();
+
+        // Keep the pool busy, everything chained below has to queue up behind this.
+        executor.execute(move || {
+            id_tx.send(thread::current().id()).unwrap();
+            let _ = rx.recv();
+        });
+
+        let f: Future<Vec<thread::ThreadId>> = Future::new_on(&executor, || Async::Ok(vec![thread::current().id()]))
+            .map(|mut ids| { ids.push(thread::current().id()); ids })
+            .and_then(|mut ids| { ids.push(thread::current().id()); Async::Ok(ids) });
+
+        // The steps after the first are queued once the value before them arrives.
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(pool.metrics().queued, 1);
+
+        tx.send(()).unwrap();
+        let pool_thread = id_rx.recv().unwrap();
+        assert_eq!(f.recv(), Ok(vec![pool_thread; 3]));
+    }
+}
@@ -0,0 +1,148 @@
+//! Sequences of values produced over time. A `Stream` is lazy, its producer only
+//! gets called once the stream is consumed with `fold` or `collect`, which pull
+//! every value on the pool and resolve a regular `Future` with the outcome.
+
+use {Async, Future};
+
+type Producer<T, E> = Box<dyn FnMut() -> Option<Result<T, E>> + Send>;
+
+/// A sequence of values, ending the first time the producer returns `None`.
+pub struct Stream<T, E=()> {
+    next: Producer<T, E>
+}
+
+impl<T, E> Stream<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// ```
+    /// use tangle::stream::Stream;
+    ///
+    /// let mut n = 0;
+    /// let s: Stream<u32> = Stream::new(move || { n += 1; if n <= 3 { Some(n) } else { None } });
+    ///
+    /// assert_eq!(s.map(|n| n * 10).collect().recv(), Ok(vec![10, 20, 30]));
+    /// ```
+    pub fn new<F>(mut f: F) -> Stream<T, E>
+        where F: FnMut() -> Option<T> + Send + 'static
+    {
+        Stream::from_results(move || f().map(Ok))
+    }
+
+    /// Same as `new`, except the producer can fail. Consuming the stream stops at
+    /// the first error.
+    pub fn from_results<F>(f: F) -> Stream<T, E>
+        where F: FnMut() -> Option<Result<T, E>> + Send + 'static
+    {
+        Stream { next: Box::new(f) }
+    }
+
+    pub fn map<U, F>(self, mut f: F) -> Stream<U, E>
+        where F: FnMut(T) -> U + Send + 'static,
+              U: Send + 'static
+    {
+        let mut next = self.next;
+
+        Stream::from_results(move || next().map(|val| val.map(&mut f)))
+    }
+
+    /// Skip every value `f` returns `false` for. Errors are always kept.
+    pub fn filter<F>(self, mut f: F) -> Stream<T, E>
+        where F: FnMut(&T) -> bool + Send + 'static
+    {
+        let mut next = self.next;
+
+        Stream::from_results(move || {
+            loop {
+                match next() {
+                    Some(Ok(val)) => if f(&val) { return Some(Ok(val)) },
+                    other => return other
+                }
+            }
+        })
+    }
+
+    /// Combine every value into `init` with `f`, on the pool.
+    ///
+    /// ```
+    /// use tangle::stream::Stream;
+    ///
+    /// let mut n = 0;
+    /// let s: Stream<u32> = Stream::new(move || { n += 1; if n <= 4 { Some(n) } else { None } });
+    ///
+    /// assert_eq!(s.fold(0, |sum, n| sum + n).recv(), Ok(10));
+    /// ```
+    pub fn fold<A, F>(self, init: A, mut f: F) -> Future<A, E>
+        where F: FnMut(A, T) -> A + Send + 'static,
+              A: Send + 'static
+    {
+        let mut next = self.next;
+
+        Future::new(move || {
+            let mut acc = init;
+
+            while let Some(val) = next() {
+                match val {
+                    Ok(val) => acc = f(acc, val),
+                    Err(err) => return Async::Err(err)
+                }
+            }
+
+            Async::Ok(acc)
+        })
+    }
+
+    pub fn collect(self) -> Future<Vec<T>, E> {
+        self.fold(Vec::new(), |mut values, val| {
+            values.push(val);
+            values
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn count_to(max: u32) -> Stream<u32, &'static str> {
+        let mut n = 0;
+
+        Stream::new(move || {
+            n += 1;
+            if n <= max { Some(n) } else { None }
+        })
+    }
+
+    #[test]
+    fn filter_and_map() {
+        let f = count_to(6).filter(|n| n % 2 == 0).map(|n| n * n).collect();
+
+        assert_eq!(f.recv(), Ok(vec![4, 16, 36]));
+    }
+
+    #[test]
+    fn stops_at_error() {
+        let mut n = 0;
+        let s: Stream<u32, &str> = Stream::from_results(move || {
+            n += 1;
+            match n {
+                1 => Some(Ok(1)),
+                2 => Some(Err("broken pipe")),
+                _ => panic!("Unexpected pull after the error")
+            }
+        });
+
+        assert_eq!(s.collect().recv(), Err("broken pipe"));
+    }
+
+    #[test]
+    fn lazy_until_consumed() {
+        let (tx, rx) = channel();
+        let s: Stream<u32> = Stream::new(move || { tx.send(()).unwrap(); None });
+
+        assert!(rx.try_recv().is_err());
+        assert_eq!(s.collect().recv(), Ok(vec![]));
+        assert!(rx.try_recv().is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+//! Helpers for writing deterministic unit tests against futures. Only compiled
+//! for the crate's own tests or when the `testing` feature is enabled.
+
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use {Async, Future};
+
+/// Constructors for futures that are already resolved.
+pub struct MockFuture;
+
+impl MockFuture {
+    /// A future that has already succeeded with `val`, same as `Future::unit`.
+    ///
+    /// ```
+    /// use tangle::Future;
+    /// use tangle::testing::MockFuture;
+    ///
+    /// let f: Future<usize> = MockFuture::resolved(5);
+    /// assert_eq!(f.recv().unwrap(), 5);
+    /// ```
+    pub fn resolved<T, E>(val: T) -> Future<T, E>
+        where T: Send + 'static,
+              E: Send + 'static
+    {
+        Future::unit(val)
+    }
+
+    /// A future that has already failed with `err`, same as `Future::err`.
+    pub fn failed<T, E>(err: E) -> Future<T, E>
+        where T: Send + 'static,
+              E: Send + 'static
+    {
+        Future::err(err)
+    }
+}
+
+/// A future that only resolves once its paired `FutureController` says so. No
+/// pool thread is involved until the future is composed.
+#[derive(Debug)]
+pub struct ControlledFuture<T, E=()> {
+    future: Future<T, E>
+}
+
+impl<T, E> ControlledFuture<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// ```
+    /// use tangle::testing::ControlledFuture;
+    ///
+    /// let (future, controller) = ControlledFuture::<u32>::new();
+    ///
+    /// controller.resolve(123);
+    /// assert_eq!(future.recv().unwrap(), 123);
+    /// ```
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> (ControlledFuture<T, E>, FutureController<T, E>) {
+        let (tx, rx) = channel();
+
+        (ControlledFuture { future: Future::from_async_channel(rx) }, FutureController { chan: tx })
+    }
+
+    pub fn into_future(self) -> Future<T, E> {
+        self.future
+    }
+
+    pub fn recv(self) -> Result<T, E> {
+        self.future.recv()
+    }
+}
+
+/// The resolving half of a `ControlledFuture`.
+#[derive(Debug)]
+pub struct FutureController<T, E=()> {
+    chan: Sender<Async<T, E>>
+}
+
+impl<T, E> FutureController<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    pub fn resolve(self, val: T) {
+        let _ = self.chan.send(Async::Ok(val));
+    }
+
+    pub fn reject(self, err: E) {
+        let _ = self.chan.send(Async::Err(err));
+    }
+
+    /// Resolve the future with `val` once `delay` has elapsed. The wait happens on
+    /// a dedicated thread so the pool stays free for the code under test.
+    pub fn resolve_after(self, delay: Duration, val: T) {
+        thread::spawn(move || {
+            thread::sleep(delay);
+            self.resolve(val);
+        });
+    }
+}
+
+impl<T, E> Future<T, E>
+    where T: Send + 'static,
+          E: Send + 'static
+{
+    /// Wait for the result, panicking if it takes longer than `dur`.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tangle::Future;
+    ///
+    /// let f: Future<u32> = Future::unit(5);
+    /// assert_eq!(f.assert_completed_within(Duration::from_secs(1)).unwrap(), 5);
+    /// ```
+    pub fn assert_completed_within(self, dur: Duration) -> Async<T, E> {
+        match self.await_until(Instant::now() + dur) {
+            Some(val) => Async::lift_result(val),
+            None => panic!("future did not complete within {:?}.", dur)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Future;
+    use std::time::Duration;
+
+    #[test]
+    fn mock_resolved() {
+        let f: Future<u32> = MockFuture::resolved(5);
+        assert_eq!(f.recv().unwrap(), 5);
+    }
+
+    #[test]
+    fn mock_failed() {
+        let f: Future<u32, &str> = MockFuture::failed("boom");
+        assert_eq!(f.recv(), Err("boom"));
+    }
+
+    #[test]
+    fn controlled_composes() {
+        let (future, controller) = ControlledFuture::<u32>::new();
+        let f = future.into_future().map(|n| n * 2);
+
+        controller.resolve(21);
+
+        assert_eq!(f.recv().unwrap(), 42);
+    }
+
+    #[test]
+    fn controlled_reject() {
+        let (future, controller) = ControlledFuture::<u32, u32>::new();
+
+        controller.reject(7);
+
+        assert_eq!(future.recv(), Err(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "did not complete")]
+    fn completed_within_pending() {
+        let (future, _controller) = ControlledFuture::<u32>::new();
+
+        future.into_future().assert_completed_within(Duration::from_millis(10));
+    }
+
+    #[test]
+    fn resolve_after_delay() {
+        let (future, controller) = ControlledFuture::<u32>::new();
+
+        controller.resolve_after(Duration::from_millis(10), 9);
+
+        assert_eq!(future.recv().unwrap(), 9);
+    }
+}